@@ -0,0 +1 @@
+DROP TABLE IF EXISTS snapshots_batch_jobs;
@@ -0,0 +1,9 @@
+CREATE TABLE snapshots_batch_jobs (
+  id UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+  batch_id UUID NOT NULL,
+  status VARCHAR(255) NOT NULL,
+  progress DOUBLE PRECISION NOT NULL DEFAULT 0,
+  error TEXT,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
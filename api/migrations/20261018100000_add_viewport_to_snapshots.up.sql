@@ -0,0 +1,2 @@
+ALTER TABLE snapshots
+ADD COLUMN viewport VARCHAR(255);
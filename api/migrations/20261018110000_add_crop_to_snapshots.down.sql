@@ -0,0 +1,5 @@
+ALTER TABLE snapshots
+    DROP COLUMN IF EXISTS crop_x,
+    DROP COLUMN IF EXISTS crop_y,
+    DROP COLUMN IF EXISTS crop_width,
+    DROP COLUMN IF EXISTS crop_height;
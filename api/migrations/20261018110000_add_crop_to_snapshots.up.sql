@@ -0,0 +1,5 @@
+ALTER TABLE snapshots
+    ADD COLUMN crop_x DOUBLE PRECISION,
+    ADD COLUMN crop_y DOUBLE PRECISION,
+    ADD COLUMN crop_width DOUBLE PRECISION,
+    ADD COLUMN crop_height DOUBLE PRECISION;
@@ -0,0 +1 @@
+ALTER TABLE snapshots ADD COLUMN masks JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,2 @@
+ALTER TABLE snapshots
+ADD COLUMN browser VARCHAR(50) NOT NULL DEFAULT 'chrome';
@@ -0,0 +1 @@
+ALTER TABLE snapshots ADD COLUMN globals JSONB NOT NULL DEFAULT '{}';
@@ -0,0 +1 @@
+ALTER TABLE snapshots ADD COLUMN device_pixel_ratio DOUBLE PRECISION NOT NULL DEFAULT 1;
@@ -0,0 +1 @@
+ALTER TABLE snapshots_batches DROP COLUMN IF EXISTS filter;
@@ -0,0 +1 @@
+ALTER TABLE snapshots_batches ADD COLUMN filter JSONB NOT NULL DEFAULT '{}';
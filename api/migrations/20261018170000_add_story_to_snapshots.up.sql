@@ -0,0 +1 @@
+ALTER TABLE snapshots ADD COLUMN story JSONB;
@@ -0,0 +1,2 @@
+ALTER TABLE snapshots ALTER COLUMN name TYPE VARCHAR(255);
+ALTER TABLE snapshots ALTER COLUMN path TYPE VARCHAR(255);
@@ -0,0 +1,2 @@
+ALTER TABLE snapshots ALTER COLUMN name TYPE TEXT;
+ALTER TABLE snapshots ALTER COLUMN path TYPE TEXT;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS snapshots_batch_jobs_batch_id_idx;
+ALTER TABLE snapshots_batch_jobs DROP CONSTRAINT IF EXISTS snapshots_batch_jobs_batch_id_fkey;
@@ -0,0 +1,8 @@
+DELETE FROM snapshots_batch_jobs
+WHERE batch_id NOT IN (SELECT id FROM snapshots_batches);
+
+ALTER TABLE snapshots_batch_jobs
+ADD CONSTRAINT snapshots_batch_jobs_batch_id_fkey
+FOREIGN KEY (batch_id) REFERENCES snapshots_batches(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS snapshots_batch_jobs_batch_id_idx ON snapshots_batch_jobs (batch_id);
@@ -9,7 +9,7 @@ use tower_http::{
 use tracing::Level;
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::{models::app_state::AppState, utils::env_variables};
+use crate::{models::app_state::AppState, service::snapshot_job_service};
 
 pub mod errors;
 pub mod extractors;
@@ -18,7 +18,7 @@ pub mod swagger_config;
 
 pub async fn serve() {
     let app_state: Arc<AppState> = Arc::new(AppState::new().await);
-    let env_variables = env_variables::EnvVariables::new();
+    let env_variables = &app_state.env_variables;
 
     tracing_subscriber::FmtSubscriber::builder()
         .with_max_level(tracing_subscriber::filter::LevelFilter::INFO)
@@ -45,16 +45,48 @@ pub async fn serve() {
         }
     };
 
+    if let Err(e) = snapshot_job_service::fail_unfinished_jobs(&app_state.db_pool).await {
+        tracing::error!("Failed to clean up unfinished jobs: {}", e);
+    }
+
     tracing::info!("Server Started on {}", listener.local_addr().unwrap());
 
-    let app = create_routes(app_state);
+    let app = create_routes(app_state.clone());
 
     axum::serve(
         listener,
         app.layer(crete_trace_layer()).layer(create_cors_layer()),
     )
+    .with_graceful_shutdown(shutdown_signal())
     .await
     .unwrap();
+
+    app_state.session_pool.close().await;
+    tracing::info!("Server stopped");
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for ctrl+c");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for the terminate signal")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
 
 fn crete_trace_layer(
@@ -73,13 +105,15 @@ fn create_cors_layer() -> CorsLayer {
 }
 
 fn create_routes(app_state: Arc<AppState>) -> Router {
-    let env_variables = env_variables::EnvVariables::new();
-
     Router::new()
         .route("/ping", get(routes::handle_ping::handler))
-        .nest_service("/api/assets", ServeDir::new(env_variables.assets_folder))
+        .nest_service(
+            "/api/assets",
+            ServeDir::new(&app_state.env_variables.assets_folder),
+        )
         .nest("/api/snap-shots", routes::handle_snapshot::router())
         .nest("/api/admin", routes::handle_admin::router())
+        .nest("/api/jobs", routes::handle_jobs::router())
         .merge(
             SwaggerUi::new("/swagger-ui")
                 .url("/api-docs/openapi.json", swagger_config::generate_doc()),
@@ -10,9 +10,9 @@ use utoipa::OpenApi;
 
 use crate::{
     api::errors::AppError,
-    db::snapshot_store,
+    db::{snapshot_batch_job_store, snapshot_store},
     models::    app_state::AppState,
-    service::snapshot_history_service, utils::env_variables::EnvVariables,
+    service::snapshot_history_service,
 };
 
 #[derive(OpenApi)]
@@ -39,8 +39,10 @@ async fn handle_clean_up(State(state): State<Arc<AppState>>) -> Result<(), AppEr
     snapshot_history_service::delete_all_batches(state.db_pool.clone()).await?;
     // Remove all josb
     snapshot_store::delete_all_snapshots(&state.db_pool).await?;
+    // Remove all batch jobs
+    snapshot_batch_job_store::delete_all_snap_shot_batch_jobs(&state.db_pool).await?;
 
-    let folder_path = EnvVariables::new().assets_folder;
+    let folder_path = state.env_variables.assets_folder.clone();
     
     match fs::exists(&folder_path) {
         Ok(_) => {
@@ -0,0 +1,47 @@
+use axum::extract::{Path, State};
+use axum::{routing, Json, Router};
+
+use std::sync::Arc;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+use crate::api::errors::AppError;
+use crate::models::app_state::AppState;
+use crate::models::snapshot_batch_job::{SnapShotBatchJob, SnapShotBatchJobStatus};
+use crate::service::snapshot_job_service;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(handle_get_job_by_id),
+    components(schemas(SnapShotBatchJob, SnapShotBatchJobStatus)),
+    tags((name = "Jobs", description = "Snap shot batch jobs"))
+)]
+pub struct JobsDoc;
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new().route("/:id", routing::get(handle_get_job_by_id))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}",
+    params(("id", description = "Job Id")),
+    responses(
+        (status = 200, description = "Get snap shot batch job by id", body = SnapShotBatchJob),
+        (status = 404, description = "Job not found"),
+    ),
+    tag="Jobs"
+
+)]
+async fn handle_get_job_by_id(
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SnapShotBatchJob>, AppError> {
+    match snapshot_job_service::get_snapshot_batch_job_by_id(id, &state.db_pool).await? {
+        Some(job) => Ok(Json(job)),
+        None => Err(AppError(
+            anyhow::Error::msg(format!("Snap shot batch job with id {} not found", id)),
+            axum::http::StatusCode::NOT_FOUND,
+        )),
+    }
+}
@@ -1,24 +1,93 @@
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::{routing, Json, Router};
 
+use futures_util::stream::{self, BoxStream};
+use futures_util::StreamExt;
 use serde::Deserialize;
 use std::sync::Arc;
 use utoipa::{OpenApi, ToSchema};
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 use crate::api::errors::AppError;
 use crate::api::extractors::ValidateJson;
 use crate::models::app_state::AppState;
-use crate::models::snapshot_batch::{DiffImage, SnapShotBatch, SnapShotBatchImage};
-use crate::service::{snapshot_history_service, snapshot_service};
+use crate::models::args_variant::ArgsVariant;
+use crate::models::browser::Browser;
+use crate::models::capture_options::CaptureOptions;
+use crate::models::comparison::{Comparison, SnapShotBatchRequest};
+use crate::models::crop::{Crop, CropRect};
+use crate::models::device::{Device, DeviceProfile};
+use crate::models::full_page::FullPage;
+use crate::models::interaction::{Interaction, InteractionState};
+use crate::models::mask::{Mask, MaskedRegion};
+use crate::models::media::MediaEmulation;
+use crate::models::readiness_strategy::ReadinessStrategy;
+use crate::models::snapshot::SnapShotType;
+use crate::models::snapshot_batch::{
+    DiffImage, IncomparableImage, SnapShotBatch, SnapShotBatchImage,
+};
+use crate::models::snapshot_batch_event::{CompareOutcome, SnapShotBatchEvent};
+use crate::models::snapshot_batch_job::{SnapShotBatchJob, SnapShotBatchJobStatus};
+use crate::models::stability::Stability;
+use crate::models::stabilization::Stabilization;
+use crate::models::story::{Story, StoryKind};
+use crate::models::story_filter::StoryFilter;
+use crate::models::story_tree::{StoryImage, StoryImageChange, StoryTreeNode, StoryTreeStory};
+use crate::models::viewport::Viewport;
+use crate::service::{snapshot_history_service, snapshot_job_service};
+use crate::utils::batch_events;
 
 #[derive(OpenApi)]
 #[openapi(
-    paths(handle_snapshot, handle_get_snapshot_history, handle_get_snapshot_by_id),
+    paths(
+        handle_snapshot,
+        handle_cross_browser_snapshot,
+        handle_get_snapshot_history,
+        handle_get_snapshot_by_id,
+        handle_get_snapshot_events,
+        handle_cancel_snapshot
+    ),
     components(
-        schemas(SnapShotParams, SnapShotBatch, DiffImage, SnapShotBatchImage),
+        schemas(
+            SnapShotParams,
+            CrossBrowserSnapShotParams,
+            CaptureOptions,
+            Viewport,
+            Device,
+            DeviceProfile,
+            Browser,
+            ArgsVariant,
+            Interaction,
+            InteractionState,
+            MediaEmulation,
+            ReadinessStrategy,
+            Crop,
+            CropRect,
+            FullPage,
+            Mask,
+            MaskedRegion,
+            Stabilization,
+            Stability,
+            StoryFilter,
+            SnapShotBatch,
+            DiffImage,
+            SnapShotBatchImage,
+            IncomparableImage,
+            Story,
+            StoryTreeNode,
+            StoryKind,
+            StoryTreeStory,
+            StoryImage,
+            StoryImageChange,
+            SnapShotBatchJob,
+            SnapShotBatchJobStatus,
+            SnapShotBatchEvent,
+            CompareOutcome,
+            SnapShotType
+        ),
     ),
     tags((name = "Snapshot", description = "All about jobs"))
 )]
@@ -27,9 +96,15 @@ pub struct SnapshotDoc;
 pub fn router() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", routing::post(handle_snapshot))
+        .route(
+            "/cross-browser",
+            routing::post(handle_cross_browser_snapshot),
+        )
         .route("/", routing::get(handle_get_snapshot_history))
         .route("/:id", routing::get(handle_get_snapshot_by_id))
         .route("/:id", routing::delete(handle_delete_snapshot_by_id))
+        .route("/:id/events", routing::get(handle_get_snapshot_events))
+        .route("/:id/cancel", routing::post(handle_cancel_snapshot))
 }
 
 #[derive(Debug, Deserialize, ToSchema, Validate)]
@@ -38,6 +113,49 @@ pub struct SnapShotParams {
     new: String,
     #[validate(url)]
     old: String,
+    #[serde(flatten)]
+    #[validate(nested)]
+    options: CaptureOptions,
+}
+
+/// Compares how one Storybook version renders in two browsers
+#[derive(Debug, Deserialize, ToSchema, Validate)]
+#[validate(schema(function = "validate_cross_browser_snapshot_params"))]
+pub struct CrossBrowserSnapShotParams {
+    #[validate(url)]
+    url: String,
+    /// Browser the candidate is compared against, stored in place of the old version
+    baseline: Browser,
+    /// Stored in place of the new version
+    candidate: Browser,
+    #[serde(flatten)]
+    #[validate(nested)]
+    options: CaptureOptions,
+}
+
+fn validate_cross_browser_snapshot_params(
+    params: &CrossBrowserSnapShotParams,
+) -> Result<(), ValidationError> {
+    if params.baseline == params.candidate {
+        return Err(ValidationError::new(
+            "baseline and candidate must be different browsers",
+        ));
+    }
+
+    if !params.options.browsers.is_empty() {
+        return Err(ValidationError::new(
+            "browsers are given by baseline and candidate",
+        ));
+    }
+
+    // Firefox would fail every capture of an emulated media, as on the other endpoint
+    if !params.options.media.is_empty()
+        && [params.baseline, params.candidate].contains(&Browser::Firefox)
+    {
+        return Err(ValidationError::new("media can not be emulated in firefox"));
+    }
+
+    Ok(())
 }
 
 #[utoipa::path(
@@ -45,7 +163,7 @@ pub struct SnapShotParams {
     path = "/api/snap-shots",
     request_body = SnapShotParams,
     responses(
-        (status = 200, description = "Creates snap shots", body = SnapShotBatch),
+        (status = 202, description = "Queues a job that creates snap shots", body = SnapShotBatchJob),
     ),
     tag="Snapshot"
 
@@ -53,10 +171,60 @@ pub struct SnapShotParams {
 pub async fn handle_snapshot(
     State(state): State<Arc<AppState>>,
     ValidateJson(payload): ValidateJson<SnapShotParams>,
-) -> Result<SnapShotBatch, AppError> {
-    snapshot_service::create_snapshots(payload.new.as_str(), payload.old.as_str(), &state.db_pool)
-        .await
-        .map_err(|e| AppError(e, StatusCode::INTERNAL_SERVER_ERROR))
+) -> Result<(StatusCode, Json<SnapShotBatchJob>), AppError> {
+    let job = snapshot_job_service::create_snapshot_batch_job(
+        SnapShotBatchRequest {
+            comparison: Comparison::Versions {
+                new_url: payload.new,
+                old_url: payload.old,
+            },
+            options: payload.options,
+        },
+        &state.db_pool,
+        &state.batch_events,
+        &state.batch_cancellations,
+        &state.session_pool,
+        &state.env_variables,
+    )
+    .await
+    .map_err(|e| AppError(e, StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/snap-shots/cross-browser",
+    request_body = CrossBrowserSnapShotParams,
+    responses(
+        (status = 202, description = "Queues a job that compares two browsers with each other", body = SnapShotBatchJob),
+    ),
+    tag="Snapshot"
+
+)]
+pub async fn handle_cross_browser_snapshot(
+    State(state): State<Arc<AppState>>,
+    ValidateJson(payload): ValidateJson<CrossBrowserSnapShotParams>,
+) -> Result<(StatusCode, Json<SnapShotBatchJob>), AppError> {
+    let job = snapshot_job_service::create_snapshot_batch_job(
+        SnapShotBatchRequest {
+            comparison: Comparison::CrossBrowser {
+                url: payload.url,
+                baseline: payload.baseline,
+                candidate: payload.candidate,
+            },
+            options: payload.options,
+        },
+        &state.db_pool,
+        &state.batch_events,
+        &state.batch_cancellations,
+        &state.session_pool,
+        &state.env_variables,
+    )
+    .await
+    .map_err(|e| AppError(e, StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
 }
 
 #[utoipa::path(
@@ -129,3 +297,120 @@ async fn handle_delete_snapshot_by_id(
         )),
     }
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/snap-shots/{id}/events",
+    params(("id", description = "Historical Item Id")),
+    responses(
+        (status = 200, description = "Stream of server sent events for the batch, ending with a summary", body = SnapShotBatchEvent, content_type = "text/event-stream"),
+        (status = 404, description = "Snap shot batch not found"),
+    ),
+    tag="Snapshot"
+
+)]
+async fn handle_get_snapshot_events(
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Sse<BoxStream<'static, Result<Event, axum::Error>>>, AppError> {
+    let events: BoxStream<'static, SnapShotBatchEvent> = match state.batch_events.subscribe(&id) {
+        Some(receiver) => batch_events::into_stream(receiver).boxed(),
+        None => match snapshot_job_service::get_snapshot_batch_summary(id, &state.db_pool).await? {
+            Some(summary) => stream::iter(vec![summary]).boxed(),
+            None => {
+                return Err(AppError(
+                    anyhow::Error::msg(format!("Snap shot batch with id {} not found", id)),
+                    axum::http::StatusCode::NOT_FOUND,
+                ))
+            }
+        },
+    };
+
+    let events = events
+        .map(|event| Event::default().event(event.name()).json_data(&event))
+        .boxed();
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/snap-shots/{id}/cancel",
+    params(("id", description = "Historical Item Id")),
+    responses(
+        (status = 202, description = "Cancels the running snap shot batch", body = SnapShotBatchJob),
+        (status = 404, description = "Snap shot batch not found"),
+        (status = 409, description = "Snap shot batch is not running"),
+    ),
+    tag="Snapshot"
+
+)]
+async fn handle_cancel_snapshot(
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<SnapShotBatchJob>), AppError> {
+    let job =
+        snapshot_job_service::cancel_snapshot_batch(id, &state.db_pool, &state.batch_cancellations)
+            .await?;
+
+    match job {
+        Some(job)
+            if job.status == SnapShotBatchJobStatus::Pending
+                || job.status == SnapShotBatchJobStatus::Processing =>
+        {
+            Ok((StatusCode::ACCEPTED, Json(job)))
+        }
+        Some(job) => Err(AppError(
+            anyhow::Error::msg(format!(
+                "Snap shot batch with id {} is not running, it is {}",
+                id, job.status
+            )),
+            StatusCode::CONFLICT,
+        )),
+        None => Err(AppError(
+            anyhow::Error::msg(format!("Snap shot batch with id {} not found", id)),
+            StatusCode::NOT_FOUND,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_cross_browser_snapshot_params_media_in_firefox() {
+        let params = |baseline: Browser, candidate: Browser, media: Vec<MediaEmulation>| {
+            CrossBrowserSnapShotParams {
+                url: "http://localhost:6006".to_string(),
+                baseline,
+                candidate,
+                options: CaptureOptions {
+                    media,
+                    ..Default::default()
+                },
+            }
+        };
+
+        assert!(params(
+            Browser::Chrome,
+            Browser::Firefox,
+            vec![MediaEmulation::Dark]
+        )
+        .validate()
+        .is_err());
+        assert!(
+            params(Browser::Firefox, Browser::Edge, vec![MediaEmulation::Print])
+                .validate()
+                .is_err()
+        );
+        assert!(params(Browser::Chrome, Browser::Firefox, vec![])
+            .validate()
+            .is_ok());
+        assert!(
+            params(Browser::Chrome, Browser::Edge, vec![MediaEmulation::Dark])
+                .validate()
+                .is_ok()
+        );
+    }
+}
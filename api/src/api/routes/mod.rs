@@ -1,3 +1,4 @@
 pub mod handle_ping;
 pub mod handle_snapshot;
-pub mod handle_admin;
\ No newline at end of file
+pub mod handle_admin;
+pub mod handle_jobs;
\ No newline at end of file
@@ -1,4 +1,4 @@
-use super::routes::handle_snapshot;
+use super::routes::{handle_jobs, handle_snapshot};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -29,6 +29,7 @@ pub fn generate_doc() -> utoipa::openapi::OpenApi {
     let mut doc: utoipa::openapi::OpenApi = ApiDoc::openapi();
 
     doc.merge(handle_snapshot::SnapshotDoc::openapi());
+    doc.merge(handle_jobs::JobsDoc::openapi());
 
     doc
 }
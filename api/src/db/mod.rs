@@ -1,4 +1,5 @@
 pub mod connection;
+pub mod snapshot_batch_job_store;
 pub mod snapshot_batch_store;
 pub mod snapshot_store;
 pub mod migrator;
\ No newline at end of file
@@ -0,0 +1,336 @@
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::models::snapshot_batch_job::{SnapShotBatchJob, SnapShotBatchJobStatus};
+
+pub async fn insert_snap_shot_batch_job(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    batch_id: &Uuid,
+) -> Result<SnapShotBatchJob, anyhow::Error> {
+    let sql = r"
+    INSERT INTO snapshots_batch_jobs (
+            batch_id,
+            status,
+            progress
+        )
+    VALUES ($1, $2, 0)
+    RETURNING *;
+    ";
+
+    let job = sqlx::query_as::<_, SnapShotBatchJob>(sql)
+        .bind(batch_id)
+        .bind(SnapShotBatchJobStatus::Pending.to_string())
+        .fetch_one(&mut **transaction)
+        .await
+        .map_err(|err| {
+            tracing::error!("Cannot insert snap shot batch job [{}]", err.to_string());
+            anyhow::Error::from(err)
+        })?;
+
+    Ok(job)
+}
+
+pub async fn get_snap_shot_batch_job_by_id(
+    pool: &Pool<Postgres>,
+    id: &Uuid,
+) -> Result<Option<SnapShotBatchJob>, anyhow::Error> {
+    let sql = r"
+    SELECT * FROM snapshots_batch_jobs
+    WHERE id = $1
+    ";
+
+    let job = sqlx::query_as::<_, SnapShotBatchJob>(sql)
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|err| {
+            tracing::error!("Cannot get snap shot batch job [{}]", err.to_string());
+            anyhow::Error::from(err)
+        })?;
+
+    Ok(job)
+}
+
+pub async fn get_snap_shot_batch_job_by_batch_id(
+    pool: &Pool<Postgres>,
+    batch_id: &Uuid,
+) -> Result<Option<SnapShotBatchJob>, anyhow::Error> {
+    let sql = r"
+    SELECT * FROM snapshots_batch_jobs
+    WHERE batch_id = $1
+    ORDER BY created_at DESC
+    LIMIT 1
+    ";
+
+    let job = sqlx::query_as::<_, SnapShotBatchJob>(sql)
+        .bind(batch_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|err| {
+            tracing::error!("Cannot get snap shot batch job [{}]", err.to_string());
+            anyhow::Error::from(err)
+        })?;
+
+    Ok(job)
+}
+
+pub async fn update_snap_shot_batch_job_status(
+    pool: &Pool<Postgres>,
+    id: &Uuid,
+    status: SnapShotBatchJobStatus,
+    error: Option<String>,
+) -> Result<Option<SnapShotBatchJob>, anyhow::Error> {
+    let sql = r"
+    UPDATE snapshots_batch_jobs
+    SET status = $2,
+        error = $3,
+        progress = CASE WHEN $2 = 'Completed' THEN 100 ELSE progress END,
+        updated_at = CURRENT_TIMESTAMP
+    WHERE id = $1
+    RETURNING *;
+    ";
+
+    let job = sqlx::query_as::<_, SnapShotBatchJob>(sql)
+        .bind(id)
+        .bind(status.to_string())
+        .bind(error)
+        .fetch_optional(pool)
+        .await
+        .map_err(|err| {
+            tracing::error!(
+                "Cannot update snap shot batch job status [{}]",
+                err.to_string()
+            );
+            anyhow::Error::from(err)
+        })?;
+
+    Ok(job)
+}
+
+pub async fn update_snap_shot_batch_job_progress(
+    pool: &Pool<Postgres>,
+    id: &Uuid,
+    progress: f64,
+) -> Result<(), anyhow::Error> {
+    let sql = r"
+    UPDATE snapshots_batch_jobs
+    SET progress = $2,
+        updated_at = CURRENT_TIMESTAMP
+    WHERE id = $1
+    ";
+
+    sqlx::query(sql)
+        .bind(id)
+        .bind(progress)
+        .execute(pool)
+        .await
+        .map_err(|err| {
+            tracing::error!(
+                "Cannot update snap shot batch job progress [{}]",
+                err.to_string()
+            );
+            anyhow::Error::from(err)
+        })?;
+
+    Ok(())
+}
+
+/// Marks jobs left `Pending` or `Processing` by a previous run of the server as failed,
+/// since nothing is working on them anymore.
+pub async fn fail_unfinished_snap_shot_batch_jobs(
+    pool: &Pool<Postgres>,
+) -> Result<Vec<SnapShotBatchJob>, anyhow::Error> {
+    let sql = r"
+    UPDATE snapshots_batch_jobs
+    SET status = $1,
+        error = 'Interrupted by a server restart',
+        updated_at = CURRENT_TIMESTAMP
+    WHERE status IN ($2, $3)
+    RETURNING *;
+    ";
+
+    let jobs = sqlx::query_as::<_, SnapShotBatchJob>(sql)
+        .bind(SnapShotBatchJobStatus::Failed.to_string())
+        .bind(SnapShotBatchJobStatus::Pending.to_string())
+        .bind(SnapShotBatchJobStatus::Processing.to_string())
+        .fetch_all(pool)
+        .await
+        .map_err(|err| {
+            tracing::error!(
+                "Cannot fail unfinished snap shot batch jobs [{}]",
+                err.to_string()
+            );
+            anyhow::Error::from(err)
+        })?;
+
+    Ok(jobs)
+}
+
+pub async fn delete_all_snap_shot_batch_jobs(pool: &Pool<Postgres>) -> Result<(), anyhow::Error> {
+    let sql = r"
+    DELETE FROM snapshots_batch_jobs
+    ";
+
+    sqlx::query(sql).execute(pool).await.map_err(|err| {
+        tracing::error!(
+            "Cannot delete all snap shot batch jobs [{}]",
+            err.to_string()
+        );
+        anyhow::Error::from(err)
+    })?;
+
+    Ok(())
+}
+
+pub async fn delete_snap_shot_batch_jobs_by_batch_id(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    batch_id: &Uuid,
+) -> Result<(), anyhow::Error> {
+    let sql = r"
+    DELETE FROM snapshots_batch_jobs
+    WHERE batch_id = $1
+    ";
+
+    sqlx::query(sql)
+        .bind(batch_id)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|err| {
+            tracing::error!(
+                "Cannot delete snap shot batch jobs by batch id [{}]",
+                err.to_string()
+            );
+            anyhow::Error::from(err)
+        })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::snapshot_batch_store,
+        models::{snapshot_batch::SnapShotBatchDTO, story_filter::StoryFilter},
+    };
+    use chrono::Utc;
+    use sqlx::PgPool;
+
+    async fn add_snapshot_batch_job(pool: &PgPool) -> SnapShotBatchJob {
+        let mut transaction: sqlx::Transaction<'_, sqlx::Postgres> = pool.begin().await.unwrap();
+        let batch = snapshot_batch_store::insert_snap_shot_batch(
+            &mut transaction,
+            &SnapShotBatchDTO {
+                id: Uuid::new_v4(),
+                created_at: Utc::now().naive_utc(),
+                name: String::from(""),
+                new_story_book_version: String::from(""),
+                old_story_book_version: String::from(""),
+                filter: StoryFilter::default(),
+            },
+        )
+        .await
+        .unwrap();
+        let job = insert_snap_shot_batch_job(&mut transaction, &batch.id)
+            .await
+            .unwrap();
+
+        let _ = transaction.commit().await;
+
+        job
+    }
+
+    #[sqlx::test]
+    async fn test_insert_snapshot_batch_job(pool: PgPool) {
+        let job = add_snapshot_batch_job(&pool).await;
+
+        assert_eq!(job.status, SnapShotBatchJobStatus::Pending);
+        assert_eq!(job.progress, 0.0);
+
+        let job_by_id = get_snap_shot_batch_job_by_id(&pool, &job.id).await.unwrap();
+
+        assert!(job_by_id.is_some());
+        assert_eq!(
+            job_by_id.unwrap().snap_shot_batch_id,
+            job.snap_shot_batch_id
+        );
+
+        let job_by_batch_id = get_snap_shot_batch_job_by_batch_id(&pool, &job.snap_shot_batch_id)
+            .await
+            .unwrap();
+
+        assert_eq!(job_by_batch_id.unwrap().id, job.id);
+    }
+
+    #[sqlx::test]
+    async fn test_snapshot_batch_job_requires_its_batch(pool: PgPool) {
+        let mut transaction: sqlx::Transaction<'_, sqlx::Postgres> = pool.begin().await.unwrap();
+
+        assert!(
+            insert_snap_shot_batch_job(&mut transaction, &Uuid::new_v4())
+                .await
+                .is_err()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_deleting_snapshot_batch_deletes_its_jobs(pool: PgPool) {
+        let job = add_snapshot_batch_job(&pool).await;
+
+        snapshot_batch_store::delete_all_snapshot_batches(&pool)
+            .await
+            .unwrap();
+
+        assert!(get_snap_shot_batch_job_by_id(&pool, &job.id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_update_snapshot_batch_job(pool: PgPool) {
+        let job = add_snapshot_batch_job(&pool).await;
+
+        update_snap_shot_batch_job_progress(&pool, &job.id, 40.0)
+            .await
+            .unwrap();
+
+        let updated = update_snap_shot_batch_job_status(
+            &pool,
+            &job.id,
+            SnapShotBatchJobStatus::Failed,
+            Some("error".to_string()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(updated.status, SnapShotBatchJobStatus::Failed);
+        assert_eq!(updated.progress, 40.0);
+        assert_eq!(updated.error, Some("error".to_string()));
+
+        let updated = update_snap_shot_batch_job_status(
+            &pool,
+            &job.id,
+            SnapShotBatchJobStatus::Completed,
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(updated.progress, 100.0);
+        assert!(updated.error.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_fail_unfinished_snapshot_batch_jobs(pool: PgPool) {
+        let job = add_snapshot_batch_job(&pool).await;
+
+        let failed = fail_unfinished_snap_shot_batch_jobs(&pool).await.unwrap();
+
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].id, job.id);
+        assert_eq!(failed[0].status, SnapShotBatchJobStatus::Failed);
+    }
+}
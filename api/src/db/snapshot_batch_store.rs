@@ -11,9 +11,10 @@ pub async fn insert_snap_shot_batch(
             name,
             created_at,
             new_story_book_version,
-            old_story_book_version
+            old_story_book_version,
+            filter
         )
-    VALUES ($1, $2, $3, $4)
+    VALUES ($1, $2, $3, $4, $5)
     RETURNING *;
     ";
 
@@ -22,6 +23,7 @@ pub async fn insert_snap_shot_batch(
         .bind(snap_shot_batch.created_at)
         .bind(snap_shot_batch.new_story_book_version.to_string())
         .bind(snap_shot_batch.old_story_book_version.to_string())
+        .bind(sqlx::types::Json(&snap_shot_batch.filter))
         .fetch_all(&mut **transaction)
         .await
         .map_err(|err| {
@@ -117,6 +119,7 @@ pub async fn delete_snapshot_batches_by_id(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::story_filter::StoryFilter;
     use chrono::Utc;
     use sqlx::PgPool;
     use uuid::Uuid;
@@ -131,6 +134,7 @@ mod tests {
                 name: format!("{}-{}", "", ""),
                 new_story_book_version: String::from(""),
                 old_story_book_version: String::from(""),
+                filter: StoryFilter::default(),
             },
         )
         .await;
@@ -151,6 +155,10 @@ mod tests {
                 name: format!("{}-{}", "", ""),
                 new_story_book_version: String::from(""),
                 old_story_book_version: String::from(""),
+                filter: StoryFilter {
+                    include: vec!["button--*".to_string()],
+                    ..Default::default()
+                },
             },
         )
         .await;
@@ -162,6 +170,7 @@ mod tests {
 
         assert_eq!(all_batches.len(), 1);
         assert_eq!(all_batches[0].id, batch.unwrap().id);
+        assert_eq!(all_batches[0].filter.include, vec!["button--*"]);
     }
 
     #[sqlx::test]
@@ -1,9 +1,9 @@
 use anyhow::Result;
 use chrono::NaiveDateTime;
-use sqlx::{Pool, Postgres};
+use sqlx::{types::Json, Pool, Postgres};
 use uuid::Uuid;
 
-use crate::models::snapshot::SnapShot;
+use crate::models::{globals::Globals, mask::MaskedRegion, snapshot::SnapShot, story::Story};
 
 pub async fn insert_snapshots(
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
@@ -17,16 +17,38 @@ pub async fn insert_snapshots(
             width,
             height,
             snap_shot_type,
-            created_at
+            created_at,
+            viewport,
+            browser,
+            globals,
+            device_pixel_ratio,
+            crop_x,
+            crop_y,
+            crop_width,
+            crop_height,
+            masks,
+            story,
+            reason
         )
     SELECT * FROM UNNEST(
         $1::UUID[],
-        $2::VARCHAR(100)[],
-        $3::VARCHAR(200)[],
+        $2::TEXT[],
+        $3::TEXT[],
         $4::DOUBLE PRECISION[],
         $5::DOUBLE PRECISION[],
         $6::VARCHAR(100)[],
-        $7::TIMESTAMP[]
+        $7::TIMESTAMP[],
+        $8::VARCHAR(255)[],
+        $9::VARCHAR(50)[],
+        $10::JSONB[],
+        $11::DOUBLE PRECISION[],
+        $12::DOUBLE PRECISION[],
+        $13::DOUBLE PRECISION[],
+        $14::DOUBLE PRECISION[],
+        $15::DOUBLE PRECISION[],
+        $16::JSONB[],
+        $17::JSONB[],
+        $18::TEXT[]
     )
     RETURNING *;";
 
@@ -68,6 +90,72 @@ pub async fn insert_snapshots(
                 .map(|s| s.created_at)
                 .collect::<Vec<NaiveDateTime>>(),
         )
+        .bind(
+            snapshots
+                .iter()
+                .map(|s| s.viewport.clone())
+                .collect::<Vec<Option<String>>>(),
+        )
+        .bind(
+            snapshots
+                .iter()
+                .map(|s| s.browser.to_string())
+                .collect::<Vec<String>>(),
+        )
+        .bind(
+            snapshots
+                .iter()
+                .map(|s| Json(s.globals.clone()))
+                .collect::<Vec<Json<Globals>>>(),
+        )
+        .bind(
+            snapshots
+                .iter()
+                .map(|s| s.device_pixel_ratio)
+                .collect::<Vec<f64>>(),
+        )
+        .bind(
+            snapshots
+                .iter()
+                .map(|s| s.crop.map(|crop| crop.x))
+                .collect::<Vec<Option<f64>>>(),
+        )
+        .bind(
+            snapshots
+                .iter()
+                .map(|s| s.crop.map(|crop| crop.y))
+                .collect::<Vec<Option<f64>>>(),
+        )
+        .bind(
+            snapshots
+                .iter()
+                .map(|s| s.crop.map(|crop| crop.width))
+                .collect::<Vec<Option<f64>>>(),
+        )
+        .bind(
+            snapshots
+                .iter()
+                .map(|s| s.crop.map(|crop| crop.height))
+                .collect::<Vec<Option<f64>>>(),
+        )
+        .bind(
+            snapshots
+                .iter()
+                .map(|s| Json(s.masks.clone()))
+                .collect::<Vec<Json<Vec<MaskedRegion>>>>(),
+        )
+        .bind(
+            snapshots
+                .iter()
+                .map(|s| s.story.clone().map(Json))
+                .collect::<Vec<Option<Json<Story>>>>(),
+        )
+        .bind(
+            snapshots
+                .iter()
+                .map(|s| s.reason.clone())
+                .collect::<Vec<Option<String>>>(),
+        )
         .fetch_all(&mut **transaction)
         .await
         .map_err(|err| {
@@ -137,7 +225,9 @@ pub async fn delete_all_snapshots_by_batch_id(
 
 #[cfg(test)]
 mod tests {
-    use crate::models::snapshot::SnapShotType;
+    use crate::models::{
+        browser::Browser, crop::CropRect, snapshot::SnapShotType, story::StoryKind,
+    };
 
     use super::*;
     use chrono::Utc;
@@ -159,7 +249,33 @@ mod tests {
                 created_at: Utc::now().naive_utc(),
                 height: 100.0,
                 width: 100.0,
-                snap_shot_type: SnapShotType::New,
+                snap_shot_type: SnapShotType::Incomparable,
+                viewport: None,
+                browser: Browser::Firefox,
+                globals: Globals::from([("theme".to_string(), "dark".to_string())]),
+                device_pixel_ratio: 2.0,
+                crop: Some(CropRect {
+                    x: 10.0,
+                    y: 10.0,
+                    width: 80.0,
+                    height: 80.0,
+                }),
+                masks: vec![MaskedRegion {
+                    selector: ".date".to_string(),
+                    x: 20.0,
+                    y: 20.0,
+                    width: 10.0,
+                    height: 10.0,
+                }],
+                story: Some(Story {
+                    id: "input--default".to_string(),
+                    title: "Components/Forms/Input".to_string(),
+                    name: "Default".to_string(),
+                    import_path: Some("./src/Input.stories.tsx".to_string()),
+                    tags: vec!["dev".to_string()],
+                    kind: StoryKind::Story,
+                }),
+                reason: Some("Captured at device pixel ratios 1 and 2".to_string()),
             }],
         )
         .await;
@@ -172,6 +288,26 @@ mod tests {
             .unwrap();
 
         assert_eq!(batch.unwrap()[0].id, snapshots_by_batch[0].id);
+        assert_eq!(snapshots_by_batch[0].crop.unwrap().width, 80.0);
+        assert_eq!(snapshots_by_batch[0].masks[0].selector, ".date");
+        assert_eq!(snapshots_by_batch[0].browser, Browser::Firefox);
+        assert_eq!(
+            snapshots_by_batch[0]
+                .story
+                .as_ref()
+                .map(|story| story.title.as_str()),
+            Some("Components/Forms/Input")
+        );
+        assert_eq!(snapshots_by_batch[0].globals["theme"], "dark");
+        assert_eq!(snapshots_by_batch[0].device_pixel_ratio, 2.0);
+        assert_eq!(
+            snapshots_by_batch[0].snap_shot_type,
+            SnapShotType::Incomparable
+        );
+        assert_eq!(
+            snapshots_by_batch[0].reason.as_deref(),
+            Some("Captured at device pixel ratios 1 and 2")
+        );
 
         assert_eq!(snapshots_by_batch.len(), 1);
 
@@ -202,6 +338,14 @@ mod tests {
                 height: 100.0,
                 width: 100.0,
                 snap_shot_type: SnapShotType::New,
+                viewport: None,
+                browser: Browser::Chrome,
+                globals: Globals::new(),
+                device_pixel_ratio: 1.0,
+                crop: None,
+                masks: vec![],
+                story: None,
+                reason: None,
             }],
         )
         .await;
@@ -216,4 +360,38 @@ mod tests {
         
 
     }
+
+    #[sqlx::test]
+    async fn test_snapshot_insert_long_names(pool: PgPool) {
+        let mut transaction: sqlx::Transaction<'_, sqlx::Postgres> = pool.begin().await.unwrap();
+
+        // Story ids with every variant, interaction, media, device and global in them
+        let name = format!("{}.png", "forms-input--default+disabled".repeat(20));
+        let batch_id = Uuid::new_v4();
+        let inserted = insert_snapshots(
+            &mut transaction,
+            &vec![SnapShot {
+                id: Uuid::new_v4(),
+                batch_id,
+                name: name.clone(),
+                path: format!("assets/1700000000-{}/new/{}", batch_id, name),
+                created_at: Utc::now().naive_utc(),
+                height: 100.0,
+                width: 100.0,
+                snap_shot_type: SnapShotType::New,
+                viewport: None,
+                browser: Browser::Chrome,
+                globals: Globals::new(),
+                device_pixel_ratio: 1.0,
+                crop: None,
+                masks: vec![],
+                story: None,
+                reason: None,
+            }],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(inserted[0].name, name);
+    }
 }
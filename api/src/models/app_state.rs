@@ -1,23 +1,39 @@
 use sqlx::{Pool, Postgres};
 
-use crate::{db::connection::create_connection_pool, utils::env_variables::EnvVariables};
+use crate::{
+    db::connection::create_connection_pool,
+    utils::{
+        batch_cancellation::SnapShotBatchCancellations, batch_events::SnapShotBatchEvents,
+        env_variables::EnvVariables, session_pool::SessionPool,
+    },
+};
 
 #[derive(Clone)]
 pub struct AppState {
+    /// Read once on startup, so that a missing variable stops the server rather than a batch
+    pub env_variables: EnvVariables,
     pub db_pool: Pool<Postgres>,
+    pub batch_events: SnapShotBatchEvents,
+    pub batch_cancellations: SnapShotBatchCancellations,
+    pub session_pool: SessionPool,
 }
 
 impl AppState {
     pub async fn new() -> Self {
         let env_variables = EnvVariables::new();
-        let db_url = env_variables.db_url;
-        let pool = create_connection_pool(&db_url).await;
+        let pool = create_connection_pool(&env_variables.db_url).await;
 
         let db_pool: Pool<Postgres> = match pool {
             Ok(pool) => pool,
             Err(err) => panic!("Cannot connect to postgres database [{}]", err.to_string()),
         };
 
-        Self { db_pool }
+        Self {
+            session_pool: SessionPool::from_env(&env_variables),
+            env_variables,
+            db_pool,
+            batch_events: SnapShotBatchEvents::default(),
+            batch_cancellations: SnapShotBatchCancellations::default(),
+        }
     }
 }
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value;
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::utils::{
+    file_safe_name::{is_file_safe_name, validate_file_safe_name},
+    glob::matches_glob,
+};
+
+lazy_static! {
+    // Storybook only accepts plain values in the args query parameter
+    static ref ARG_VALUE: Regex = Regex::new(r"^[A-Za-z0-9_.-]+$").unwrap();
+}
+
+/// Extra args a story is captured with, as its own snapshot next to the story's defaults.
+/// Covers states such as disabled or loading without writing a story for each of them
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema, Validate)]
+pub struct ArgsVariant {
+    /// Appended to the image name of the story, e.g. `button--primary+disabled`
+    #[validate(
+        length(min = 1, max = 50),
+        custom(function = "validate_file_safe_name")
+    )]
+    pub name: String,
+    /// Args passed to the story, e.g. `{ "disabled": true, "label": "Save" }`
+    #[validate(custom(function = "validate_args"))]
+    #[schema(value_type = Object)]
+    pub args: BTreeMap<String, Value>,
+    /// Ids of the stories the variant applies to
+    #[serde(default)]
+    pub stories: Vec<String>,
+    /// Titles of the stories the variant applies to, where `*` matches anything,
+    /// e.g. `Components/Button*`. Applies to every story when both lists are empty
+    #[serde(default)]
+    pub titles: Vec<String>,
+}
+
+impl ArgsVariant {
+    pub fn applies_to(&self, story_id: &str, story_title: &str) -> bool {
+        (self.stories.is_empty() && self.titles.is_empty())
+            || self.stories.iter().any(|story| story == story_id)
+            || self
+                .titles
+                .iter()
+                .any(|title| matches_glob(title, story_title))
+    }
+
+    /// Value of the `args` query parameter of a story's iframe, e.g. `disabled:!true;label:Save`
+    pub fn to_query(&self) -> String {
+        self.args
+            .iter()
+            .map(|(name, value)| format!("{}:{}", name, to_query_value(value)))
+            .collect::<Vec<String>>()
+            .join(";")
+    }
+}
+
+fn to_query_value(value: &Value) -> String {
+    match value {
+        Value::Null => "!null".to_string(),
+        Value::Bool(value) => format!("!{}", value),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn validate_args(args: &BTreeMap<String, Value>) -> Result<(), ValidationError> {
+    if args.is_empty() {
+        return Err(ValidationError::new("variants need at least one arg"));
+    }
+
+    for (name, value) in args {
+        if !is_file_safe_name(name) {
+            return Err(ValidationError::new("invalid arg name"));
+        }
+
+        let valid = match value {
+            Value::Null | Value::Bool(_) | Value::Number(_) => true,
+            Value::String(value) => ARG_VALUE.is_match(value),
+            Value::Array(_) | Value::Object(_) => false,
+        };
+
+        if !valid {
+            return Err(ValidationError::new("invalid arg value"));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn variant() -> ArgsVariant {
+        ArgsVariant {
+            name: "disabled".to_string(),
+            args: BTreeMap::from([
+                ("disabled".to_string(), json!(true)),
+                ("label".to_string(), json!("Save")),
+                ("size".to_string(), json!(2)),
+            ]),
+            stories: vec!["card--default".to_string()],
+            titles: vec!["Components/Button*".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_args_variant_applies_to() {
+        let variant = variant();
+
+        assert!(variant.applies_to("card--default", "Card"));
+        assert!(variant.applies_to("button--primary", "Components/Button"));
+        assert!(variant.applies_to("button-group--primary", "Components/ButtonGroup"));
+        assert!(!variant.applies_to("input--primary", "Components/Input"));
+        assert!(!variant.applies_to("button--primary", "Legacy/Components/Button"));
+    }
+
+    #[test]
+    fn test_args_variant_to_query() {
+        assert_eq!(variant().to_query(), "disabled:!true;label:Save;size:2");
+    }
+
+    #[test]
+    fn test_validate_args_variant() {
+        assert!(variant().validate().is_ok());
+
+        let mut invalid = variant();
+        invalid.args.insert("label".to_string(), json!("a;b:c"));
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = variant();
+        invalid.args.clear();
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = variant();
+        invalid.name = "../disabled".to_string();
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use core::fmt;
+use std::str::FromStr;
+
+use utoipa::ToSchema;
+
+/// Browser a story is captured in
+#[derive(
+    Debug,
+    serde::Deserialize,
+    serde::Serialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Default,
+    ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Browser {
+    #[default]
+    Chrome,
+    Firefox,
+    Edge,
+}
+
+impl fmt::Display for Browser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Browser::Chrome => write!(f, "chrome"),
+            Browser::Firefox => write!(f, "firefox"),
+            Browser::Edge => write!(f, "edge"),
+        }
+    }
+}
+
+impl FromStr for Browser {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "chrome" => Ok(Browser::Chrome),
+            "firefox" => Ok(Browser::Firefox),
+            "edge" => Ok(Browser::Edge),
+            _ => Err(anyhow::Error::msg(format!("Unknown browser {}", value))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_browser_round_trips_through_its_name() {
+        for browser in [Browser::Chrome, Browser::Firefox, Browser::Edge] {
+            assert_eq!(browser.to_string().parse::<Browser>().unwrap(), browser);
+            assert_eq!(
+                serde_json::to_value(browser).unwrap(),
+                serde_json::json!(browser.to_string())
+            );
+        }
+
+        assert!("safari".parse::<Browser>().is_err());
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::HashSet;
+
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use super::{
+    args_variant::ArgsVariant,
+    browser::Browser,
+    crop::Crop,
+    device::{validate_device_profiles, Device, DeviceProfile},
+    full_page::FullPage,
+    globals::{validate_globals_matrix, GlobalsMatrix},
+    interaction::Interaction,
+    mask::Mask,
+    media::MediaEmulation,
+    readiness_strategy::{validate_readiness_strategy, ReadinessStrategy},
+    stability::Stability,
+    stabilization::Stabilization,
+    story_filter::StoryFilter,
+    viewport::Viewport,
+};
+
+/// Settings applied to every story captured in a batch
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Default, ToSchema, Validate)]
+#[validate(schema(function = "validate_unique_device_names"))]
+#[validate(schema(function = "validate_media_browsers"))]
+pub struct CaptureOptions {
+    /// Stories the batch is narrowed down to. Captures every story when empty
+    #[serde(default)]
+    #[validate(nested)]
+    pub filter: StoryFilter,
+    /// Captures the MDX and autodocs pages of the Storybook as well, with `viewMode=docs`.
+    /// The filter applies to them as to stories, while args variants and interactions do not
+    #[serde(default)]
+    pub docs: bool,
+    /// Every story is captured and compared once per viewport.
+    /// When empty stories are captured at the browser's default window size.
+    #[serde(default)]
+    #[validate(nested, custom(function = "validate_unique_viewport_names"))]
+    pub viewports: Vec<Viewport>,
+    /// Devices every story is emulated on and compared once per, next to the viewports.
+    /// Either the name of a preset such as `iphone-14`, `pixel-7`, `ipad` or `desktop-2x`, or a device
+    #[serde(default)]
+    #[validate(custom(function = "validate_device_profiles"))]
+    pub devices: Vec<DeviceProfile>,
+    /// Every story is captured and compared once per browser. Defaults to Chrome
+    #[serde(default)]
+    #[validate(custom(function = "validate_unique_browsers"))]
+    pub browsers: Vec<Browser>,
+    /// Storybook globals every story is captured and compared with, once per combination of
+    /// their values. `{ "theme": ["light", "dark"], "locale": ["en", "fr"] }` makes four
+    #[serde(default)]
+    #[validate(custom(function = "validate_globals_matrix"))]
+    #[schema(value_type = BTreeMap<String, Vec<String>>)]
+    pub globals: GlobalsMatrix,
+    /// Extra args stories are captured with, each compared as a snapshot of its own
+    #[serde(default)]
+    #[validate(nested, custom(function = "validate_unique_variant_names"))]
+    pub args_variants: Vec<ArgsVariant>,
+    /// Elements hovered, focused or pressed in extra captures of the stories,
+    /// each compared as a snapshot of its own
+    #[serde(default)]
+    #[validate(nested, custom(function = "validate_distinct_interactions"))]
+    pub interactions: Vec<Interaction>,
+    /// Css media such as a dark color scheme that stories are emulated in, each compared as
+    /// a snapshot of its own next to the default rendering. Can not be combined with Firefox,
+    /// which has no way to emulate media over WebDriver
+    #[serde(default)]
+    #[validate(custom(function = "validate_unique_media"))]
+    pub media: Vec<MediaEmulation>,
+    /// What to wait for before capturing a story. Defaults to the server's `READINESS_STRATEGY`
+    #[validate(custom(function = "validate_readiness_strategy"))]
+    pub readiness: Option<ReadinessStrategy>,
+    /// How long to wait for a story to be ready. Defaults to the server's `READINESS_TIMEOUT_MS`
+    #[validate(range(min = 1, max = 120000))]
+    pub readiness_timeout_ms: Option<u64>,
+    /// Crops captures to the story root or to another element. Captures the whole page when empty
+    #[validate(nested)]
+    pub crop: Option<Crop>,
+    /// Scrolls through pages taller than the viewport and stitches them into one capture
+    #[validate(nested)]
+    pub full_page: Option<FullPage>,
+    /// Elements blacked out in every capture, or in the captures of the given stories
+    #[serde(default)]
+    #[validate(nested)]
+    pub masks: Vec<Mask>,
+    /// Makes stories render the same way every time before they are captured. Opt-in per switch
+    #[serde(default)]
+    pub stabilization: Stabilization,
+    /// Captures every story until two consecutive screenshots match. Stories that never
+    /// match are reported as unstable rather than as changed
+    #[validate(nested)]
+    pub stability: Option<Stability>,
+}
+
+fn validate_unique_viewport_names(viewports: &[Viewport]) -> Result<(), ValidationError> {
+    let mut names = HashSet::new();
+
+    if viewports
+        .iter()
+        .all(|viewport| names.insert(&viewport.name))
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("viewport names must be unique"))
+    }
+}
+
+/// Devices are captured in place of a viewport, so their names share the image name
+fn validate_unique_device_names(options: &CaptureOptions) -> Result<(), ValidationError> {
+    let mut names: HashSet<String> = options
+        .viewports
+        .iter()
+        .map(|viewport| viewport.name.clone())
+        .collect();
+
+    if options
+        .devices()
+        .into_iter()
+        .all(|device| names.insert(device.name))
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new(
+            "device names must be unique and differ from viewport names",
+        ))
+    }
+}
+
+/// Firefox would fail every capture of an emulated media, so the batch is refused up front
+fn validate_media_browsers(options: &CaptureOptions) -> Result<(), ValidationError> {
+    if !options.media.is_empty() && options.browsers.contains(&Browser::Firefox) {
+        Err(ValidationError::new("media can not be emulated in firefox"))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_unique_browsers(browsers: &[Browser]) -> Result<(), ValidationError> {
+    let mut seen = HashSet::new();
+
+    if browsers.iter().all(|browser| seen.insert(browser)) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("browsers must be unique"))
+    }
+}
+
+fn validate_unique_variant_names(variants: &[ArgsVariant]) -> Result<(), ValidationError> {
+    let mut names = HashSet::new();
+
+    if variants.iter().all(|variant| names.insert(&variant.name)) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("args variant names must be unique"))
+    }
+}
+
+fn validate_unique_media(media: &[MediaEmulation]) -> Result<(), ValidationError> {
+    let mut seen = HashSet::new();
+
+    if media.iter().all(|media| seen.insert(media)) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("media must be unique"))
+    }
+}
+
+fn validate_distinct_interactions(interactions: &[Interaction]) -> Result<(), ValidationError> {
+    let overlapping = interactions.iter().enumerate().any(|(index, interaction)| {
+        interactions[index + 1..]
+            .iter()
+            .any(|other| interaction.overlaps(other))
+    });
+
+    if overlapping {
+        Err(ValidationError::new(
+            "a story can only be captured once per interaction state",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+impl CaptureOptions {
+    /// The devices requested, with presets looked up and unknown ones left out
+    pub fn devices(&self) -> Vec<Device> {
+        self.devices
+            .iter()
+            .filter_map(|profile| profile.resolve())
+            .collect()
+    }
+
+    /// The browsers requested, or Chrome when none were
+    pub fn browsers(&self) -> Vec<Browser> {
+        if self.browsers.is_empty() {
+            vec![Browser::default()]
+        } else {
+            self.browsers.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::interaction::InteractionState;
+
+    fn viewport(name: &str) -> Viewport {
+        Viewport {
+            name: name.to_string(),
+            width: 375,
+            height: 667,
+        }
+    }
+
+    #[test]
+    fn test_validate_capture_options() {
+        let options = CaptureOptions {
+            viewports: vec![viewport("mobile"), viewport("tablet")],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_capture_options_duplicate_viewports() {
+        let options = CaptureOptions {
+            viewports: vec![viewport("mobile"), viewport("mobile")],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_capture_options_invalid_viewport_name() {
+        let options = CaptureOptions {
+            viewports: vec![viewport("../mobile")],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_capture_options_device_named_like_viewport() {
+        let options = CaptureOptions {
+            viewports: vec![viewport("ipad")],
+            devices: vec![DeviceProfile::Preset("ipad".to_string())],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_err());
+
+        let options = CaptureOptions {
+            viewports: vec![viewport("mobile")],
+            devices: vec![DeviceProfile::Preset("ipad".to_string())],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_ok());
+        assert_eq!(options.devices()[0].device_pixel_ratio, 2.0);
+    }
+
+    #[test]
+    fn test_validate_capture_options_invalid_readiness() {
+        let options = CaptureOptions {
+            readiness: Some(ReadinessStrategy::Css {
+                selector: " ".to_string(),
+            }),
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_err());
+
+        let options = CaptureOptions {
+            readiness_timeout_ms: Some(0),
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_capture_options_duplicate_browsers() {
+        let options = CaptureOptions {
+            browsers: vec![Browser::Firefox, Browser::Firefox],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_err());
+        assert_eq!(CaptureOptions::default().browsers(), vec![Browser::Chrome]);
+    }
+
+    #[test]
+    fn test_validate_capture_options_duplicate_media() {
+        let options = CaptureOptions {
+            media: vec![MediaEmulation::Dark, MediaEmulation::Dark],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_err());
+
+        let options = CaptureOptions {
+            media: vec![MediaEmulation::Dark, MediaEmulation::ForcedColors],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_capture_options_media_in_firefox() {
+        let options = CaptureOptions {
+            browsers: vec![Browser::Chrome, Browser::Firefox],
+            media: vec![MediaEmulation::Dark],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_err());
+
+        let options = CaptureOptions {
+            browsers: vec![Browser::Chrome, Browser::Edge],
+            media: vec![MediaEmulation::Dark],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_capture_options_overlapping_interactions() {
+        let hover = |stories: Vec<String>| Interaction {
+            state: InteractionState::Hover,
+            selector: "button".to_string(),
+            stories,
+        };
+
+        let options = CaptureOptions {
+            interactions: vec![hover(vec!["button--primary".to_string()]), hover(vec![])],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_err());
+
+        let options = CaptureOptions {
+            interactions: vec![
+                hover(vec!["button--primary".to_string()]),
+                hover(vec!["input--default".to_string()]),
+            ],
+            ..Default::default()
+        };
+
+        assert!(options.validate().is_ok());
+    }
+}
@@ -0,0 +1,140 @@
+use super::{browser::Browser, capture_options::CaptureOptions};
+
+/// What a batch captures as its new and as its old images
+#[derive(Debug, Clone, PartialEq)]
+pub enum Comparison {
+    /// Two Storybook versions, each captured in the browsers of the batch
+    Versions { new_url: String, old_url: String },
+    /// One Storybook version captured in two browsers. The candidate takes
+    /// the place of the new version and the baseline that of the old one
+    CrossBrowser {
+        url: String,
+        baseline: Browser,
+        candidate: Browser,
+    },
+}
+
+/// Everything a batch job is asked to capture and compare
+#[derive(Debug, Clone)]
+pub struct SnapShotBatchRequest {
+    pub comparison: Comparison,
+    pub options: CaptureOptions,
+}
+
+/// Where one side of a comparison is captured
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureTarget {
+    pub url: String,
+    /// Browser every story is captured in instead of the browsers of the batch
+    pub browser: Option<Browser>,
+}
+
+impl Comparison {
+    pub fn name(&self) -> String {
+        match self {
+            Comparison::Versions { new_url, old_url } => format!("{}-{}", new_url, old_url),
+            Comparison::CrossBrowser {
+                url,
+                baseline,
+                candidate,
+            } => format!("{}@{}-{}", url, candidate, baseline),
+        }
+    }
+
+    /// Stored as the batch's new version
+    pub fn new_label(&self) -> String {
+        match self {
+            Comparison::Versions { new_url, .. } => new_url.clone(),
+            Comparison::CrossBrowser { candidate, .. } => candidate.to_string(),
+        }
+    }
+
+    /// Stored as the batch's old version
+    pub fn old_label(&self) -> String {
+        match self {
+            Comparison::Versions { old_url, .. } => old_url.clone(),
+            Comparison::CrossBrowser { baseline, .. } => baseline.to_string(),
+        }
+    }
+
+    /// Browsers the stories end up captured in
+    pub fn browsers(&self, options: &CaptureOptions) -> Vec<Browser> {
+        match self {
+            Comparison::Versions { .. } => options.browsers(),
+            Comparison::CrossBrowser {
+                baseline,
+                candidate,
+                ..
+            } => vec![*baseline, *candidate],
+        }
+    }
+
+    pub fn new_target(&self) -> CaptureTarget {
+        match self {
+            Comparison::Versions { new_url, .. } => CaptureTarget {
+                url: new_url.clone(),
+                browser: None,
+            },
+            Comparison::CrossBrowser { url, candidate, .. } => CaptureTarget {
+                url: url.clone(),
+                browser: Some(*candidate),
+            },
+        }
+    }
+
+    pub fn old_target(&self) -> CaptureTarget {
+        match self {
+            Comparison::Versions { old_url, .. } => CaptureTarget {
+                url: old_url.clone(),
+                browser: None,
+            },
+            Comparison::CrossBrowser { url, baseline, .. } => CaptureTarget {
+                url: url.clone(),
+                browser: Some(*baseline),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cross_browser_comparison_uses_browser_labels() {
+        let comparison = Comparison::CrossBrowser {
+            url: "http://storybook".to_string(),
+            baseline: Browser::Chrome,
+            candidate: Browser::Firefox,
+        };
+
+        assert_eq!(comparison.name(), "http://storybook@firefox-chrome");
+        assert_eq!(comparison.new_label(), "firefox");
+        assert_eq!(comparison.old_label(), "chrome");
+        assert_eq!(
+            comparison.new_target(),
+            CaptureTarget {
+                url: "http://storybook".to_string(),
+                browser: Some(Browser::Firefox),
+            }
+        );
+        assert_eq!(comparison.old_target().browser, Some(Browser::Chrome));
+        assert_eq!(
+            comparison.browsers(&CaptureOptions::default()),
+            vec![Browser::Chrome, Browser::Firefox]
+        );
+    }
+
+    #[test]
+    fn test_versions_comparison_keeps_urls() {
+        let comparison = Comparison::Versions {
+            new_url: "http://new".to_string(),
+            old_url: "http://old".to_string(),
+        };
+
+        assert_eq!(comparison.name(), "http://new-http://old");
+        assert_eq!(comparison.new_label(), "http://new");
+        assert_eq!(comparison.old_target().url, "http://old");
+        assert_eq!(comparison.old_target().browser, None);
+    }
+}
@@ -0,0 +1,23 @@
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// Crops captures to a single element of the story instead of the whole page
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema, Validate)]
+pub struct Crop {
+    /// Css selector of the element to crop to. Defaults to the story root, or the docs root for docs pages
+    #[validate(length(min = 1))]
+    pub selector: Option<String>,
+    /// Space kept around the element, in css pixels
+    #[serde(default)]
+    #[validate(range(max = 1000))]
+    pub padding: u32,
+}
+
+/// Area of the page a capture was cropped to, in css pixels
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, ToSchema)]
+pub struct CropRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
@@ -0,0 +1,142 @@
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::utils::file_safe_name::validate_file_safe_name;
+
+use super::viewport::Viewport;
+
+const IPHONE_USER_AGENT: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 16_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.0 Mobile/15E148 Safari/604.1";
+const PIXEL_USER_AGENT: &str = "Mozilla/5.0 (Linux; Android 13; Pixel 7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Mobile Safari/537.36";
+const IPAD_USER_AGENT: &str = "Mozilla/5.0 (iPad; CPU OS 16_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.0 Mobile/15E148 Safari/604.1";
+
+/// Screen, pixel density and browser of a device that stories are emulated on
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema, Validate)]
+pub struct Device {
+    /// Takes the place of the viewport name in image names, e.g. `button--primary@iphone-14`
+    #[validate(
+        length(min = 1, max = 50),
+        custom(function = "validate_file_safe_name")
+    )]
+    pub name: String,
+    #[validate(range(min = 1, max = 10000))]
+    pub width: u32,
+    #[validate(range(min = 1, max = 10000))]
+    pub height: u32,
+    /// Physical pixels per css pixel, 2 or more on retina displays
+    #[serde(default = "default_device_pixel_ratio")]
+    #[validate(range(min = 0.5, max = 5.0))]
+    pub device_pixel_ratio: f64,
+    /// Replaces the user agent of the browser, e.g. to be served mobile markup
+    #[validate(length(min = 1, max = 500))]
+    pub user_agent: Option<String>,
+    /// Emulates a touch screen, so that touch events and `(pointer: coarse)` apply
+    #[serde(default)]
+    pub touch: bool,
+    /// Emulates a mobile browser, which honours the viewport meta tag
+    #[serde(default)]
+    pub mobile: bool,
+}
+
+/// A device given by the name of one of the presets, such as `iphone-14`, or in full
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema)]
+#[serde(untagged)]
+pub enum DeviceProfile {
+    Preset(String),
+    Custom(Device),
+}
+
+fn default_device_pixel_ratio() -> f64 {
+    1.0
+}
+
+impl Device {
+    /// Profiles of common devices that can be asked for by name alone
+    pub fn preset(name: &str) -> Option<Device> {
+        let device = |width, height, device_pixel_ratio, user_agent: Option<&str>| Device {
+            name: name.to_string(),
+            width,
+            height,
+            device_pixel_ratio,
+            user_agent: user_agent.map(|user_agent| user_agent.to_string()),
+            touch: user_agent.is_some(),
+            mobile: user_agent.is_some(),
+        };
+
+        match name {
+            "iphone-14" => Some(device(390, 844, 3.0, Some(IPHONE_USER_AGENT))),
+            "pixel-7" => Some(device(412, 915, 2.625, Some(PIXEL_USER_AGENT))),
+            "ipad" => Some(device(820, 1180, 2.0, Some(IPAD_USER_AGENT))),
+            "desktop-2x" => Some(device(1440, 900, 2.0, None)),
+            _ => None,
+        }
+    }
+
+    /// Size the page is captured at
+    pub fn viewport(&self) -> Viewport {
+        Viewport {
+            name: self.name.clone(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+impl DeviceProfile {
+    /// The device, or `None` for a preset that does not exist
+    pub fn resolve(&self) -> Option<Device> {
+        match self {
+            DeviceProfile::Preset(name) => Device::preset(name),
+            DeviceProfile::Custom(device) => Some(device.clone()),
+        }
+    }
+}
+
+pub fn validate_device_profiles(profiles: &[DeviceProfile]) -> Result<(), ValidationError> {
+    for profile in profiles {
+        match profile {
+            DeviceProfile::Preset(name) if Device::preset(name).is_none() => {
+                return Err(ValidationError::new("unknown device preset"));
+            }
+            DeviceProfile::Custom(device) if device.validate().is_err() => {
+                return Err(ValidationError::new("invalid device"));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_profiles_deserialize_presets_and_custom_devices() {
+        let profiles: Vec<DeviceProfile> = serde_json::from_str(
+            r#"["iphone-14", { "name": "watch", "width": 200, "height": 240 }]"#,
+        )
+        .unwrap();
+
+        let iphone = profiles[0].resolve().unwrap();
+        assert_eq!((iphone.width, iphone.device_pixel_ratio), (390, 3.0));
+        assert!(iphone.touch && iphone.mobile);
+
+        let watch = profiles[1].resolve().unwrap();
+        assert_eq!(watch.device_pixel_ratio, 1.0);
+        assert_eq!(watch.user_agent, None);
+
+        assert!(validate_device_profiles(&profiles).is_ok());
+    }
+
+    #[test]
+    fn test_validate_device_profiles() {
+        assert!(
+            validate_device_profiles(&[DeviceProfile::Preset("nokia-3310".to_string())]).is_err()
+        );
+
+        let mut device = Device::preset("desktop-2x").unwrap();
+        device.device_pixel_ratio = 10.0;
+        assert!(validate_device_profiles(&[DeviceProfile::Custom(device)]).is_err());
+    }
+}
@@ -0,0 +1,24 @@
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// Captures the whole height of the page by scrolling through it and stitching
+/// screenshots of the viewport, for stories taller than the browser window
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema, Validate)]
+pub struct FullPage {
+    /// Captures of taller pages are cut off at this height, in css pixels
+    #[serde(default = "default_max_height")]
+    #[validate(range(min = 1, max = 50000))]
+    pub max_height: u32,
+}
+
+fn default_max_height() -> u32 {
+    10000
+}
+
+impl Default for FullPage {
+    fn default() -> Self {
+        Self {
+            max_height: default_max_height(),
+        }
+    }
+}
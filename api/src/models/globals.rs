@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use validator::ValidationError;
+
+use crate::utils::file_safe_name::is_file_safe_name;
+
+/// Storybook globals a story is rendered with, such as `theme` or `locale`
+pub type Globals = BTreeMap<String, String>;
+
+/// Values to capture every story with, per global
+pub type GlobalsMatrix = BTreeMap<String, Vec<String>>;
+
+/// Every combination of the values in the matrix, e.g. `theme: [light, dark]` and
+/// `locale: [en, fr]` make four. An empty matrix makes a single combination without globals.
+pub fn combinations(matrix: &GlobalsMatrix) -> Vec<Globals> {
+    matrix
+        .iter()
+        .fold(vec![Globals::new()], |combinations, (name, values)| {
+            combinations
+                .iter()
+                .flat_map(|globals| {
+                    values.iter().map(move |value| {
+                        let mut globals = globals.clone();
+                        globals.insert(name.clone(), value.clone());
+                        globals
+                    })
+                })
+                .collect()
+        })
+}
+
+/// Value of the `globals` query parameter of a story's iframe, e.g. `locale:fr;theme:dark`
+pub fn to_query(globals: &Globals) -> String {
+    globals
+        .iter()
+        .map(|(name, value)| format!("{}:{}", name, value))
+        .collect::<Vec<String>>()
+        .join(";")
+}
+
+/// Appended to image names so that each combination is matched with itself, e.g. `,locale=fr,theme=dark`
+pub fn to_name_suffix(globals: &Globals) -> String {
+    globals
+        .iter()
+        .map(|(name, value)| format!(",{}={}", name, value))
+        .collect()
+}
+
+pub fn validate_globals_matrix(matrix: &GlobalsMatrix) -> Result<(), ValidationError> {
+    for (name, values) in matrix {
+        if !is_file_safe_name(name) {
+            return Err(ValidationError::new("invalid global name"));
+        }
+
+        if values.is_empty() {
+            return Err(ValidationError::new("globals need at least one value"));
+        }
+
+        if !values.iter().all(|value| is_file_safe_name(value)) {
+            return Err(ValidationError::new("invalid global value"));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix() -> GlobalsMatrix {
+        GlobalsMatrix::from([
+            (
+                "theme".to_string(),
+                vec!["light".to_string(), "dark".to_string()],
+            ),
+            (
+                "locale".to_string(),
+                vec!["en".to_string(), "fr".to_string()],
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_combinations() {
+        let combinations = combinations(&matrix());
+
+        assert_eq!(combinations.len(), 4);
+        assert_eq!(to_query(&combinations[0]), "locale:en;theme:light");
+        assert_eq!(to_query(&combinations[3]), "locale:fr;theme:dark");
+        assert_eq!(to_name_suffix(&combinations[1]), ",locale=en,theme=dark");
+
+        assert_eq!(
+            super::combinations(&GlobalsMatrix::new()),
+            vec![Globals::new()]
+        );
+    }
+
+    #[test]
+    fn test_validate_globals_matrix() {
+        assert!(validate_globals_matrix(&matrix()).is_ok());
+
+        let empty_values = GlobalsMatrix::from([("theme".to_string(), vec![])]);
+        assert!(validate_globals_matrix(&empty_values).is_err());
+
+        let invalid_value =
+            GlobalsMatrix::from([("theme".to_string(), vec!["dark;locale:fr".to_string()])]);
+        assert!(validate_globals_matrix(&invalid_value).is_err());
+    }
+}
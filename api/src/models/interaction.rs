@@ -0,0 +1,87 @@
+use core::fmt;
+
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// State an element is put in before the story is captured
+#[derive(
+    Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum InteractionState {
+    /// The pointer rests on the element
+    Hover,
+    /// The element is focused as if it was clicked, without a focus ring
+    Focus,
+    /// The pointer is pressed and held on the element
+    Active,
+    /// The element is focused as if it was reached with the keyboard
+    FocusVisible,
+}
+
+impl fmt::Display for InteractionState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InteractionState::Hover => write!(f, "hover"),
+            InteractionState::Focus => write!(f, "focus"),
+            InteractionState::Active => write!(f, "active"),
+            InteractionState::FocusVisible => write!(f, "focus-visible"),
+        }
+    }
+}
+
+/// Captures stories a second time with an element hovered, focused or pressed,
+/// so that interaction styling is compared as well as the idle state
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema, Validate)]
+pub struct Interaction {
+    pub state: InteractionState,
+    /// Css selector of the element to interact with. The first match is used
+    #[validate(length(min = 1))]
+    pub selector: String,
+    /// Ids of the stories the interaction applies to. Applies to every story when empty
+    #[serde(default)]
+    pub stories: Vec<String>,
+}
+
+impl Interaction {
+    pub fn applies_to(&self, story_id: &str) -> bool {
+        self.stories.is_empty() || self.stories.iter().any(|story| story == story_id)
+    }
+
+    /// Whether both interactions would put some story in the same state
+    pub fn overlaps(&self, other: &Interaction) -> bool {
+        self.state == other.state
+            && (self.stories.is_empty()
+                || other.stories.is_empty()
+                || self
+                    .stories
+                    .iter()
+                    .any(|story| other.stories.contains(story)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interaction(state: InteractionState, stories: &[&str]) -> Interaction {
+        Interaction {
+            state,
+            selector: "button".to_string(),
+            stories: stories.iter().map(|story| story.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_interaction_overlaps() {
+        let hover = interaction(InteractionState::Hover, &["button--primary"]);
+
+        assert!(hover.overlaps(&interaction(InteractionState::Hover, &[])));
+        assert!(hover.overlaps(&interaction(
+            InteractionState::Hover,
+            &["input--default", "button--primary"]
+        )));
+        assert!(!hover.overlaps(&interaction(InteractionState::Hover, &["input--default"])));
+        assert!(!hover.overlaps(&interaction(InteractionState::Focus, &[])));
+    }
+}
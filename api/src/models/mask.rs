@@ -0,0 +1,30 @@
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// Elements blacked out before capture, so that content which changes on every
+/// render such as dates or random avatars does not show up as a diff
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema, Validate)]
+pub struct Mask {
+    /// Css selector of the elements to mask
+    #[validate(length(min = 1))]
+    pub selector: String,
+    /// Ids of the stories the mask applies to. Applies to every story when empty
+    #[serde(default)]
+    pub stories: Vec<String>,
+}
+
+impl Mask {
+    pub fn applies_to(&self, story_id: &str) -> bool {
+        self.stories.is_empty() || self.stories.iter().any(|story| story == story_id)
+    }
+}
+
+/// Area of a capture that was masked, in css pixels relative to the image
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema)]
+pub struct MaskedRegion {
+    pub selector: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
@@ -0,0 +1,51 @@
+use core::fmt;
+
+use utoipa::ToSchema;
+
+/// Css media a story is rendered in, captured as a snapshot of its own next to the
+/// default rendering so that dark mode and accessibility styling are compared as well
+#[derive(
+    Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaEmulation {
+    /// `prefers-color-scheme: dark`
+    Dark,
+    /// `prefers-reduced-motion: reduce`
+    ReducedMotion,
+    /// The `print` media type, as when the page is printed
+    Print,
+    /// `forced-colors: active`, as in high contrast modes
+    ForcedColors,
+}
+
+impl fmt::Display for MediaEmulation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MediaEmulation::Dark => write!(f, "dark"),
+            MediaEmulation::ReducedMotion => write!(f, "reduced-motion"),
+            MediaEmulation::Print => write!(f, "print"),
+            MediaEmulation::ForcedColors => write!(f, "forced-colors"),
+        }
+    }
+}
+
+impl MediaEmulation {
+    /// Media type the page is rendered as, in place of `screen`
+    pub fn media_type(&self) -> Option<&'static str> {
+        match self {
+            MediaEmulation::Print => Some("print"),
+            _ => None,
+        }
+    }
+
+    /// Name and value of the media feature that is emulated
+    pub fn media_feature(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            MediaEmulation::Dark => Some(("prefers-color-scheme", "dark")),
+            MediaEmulation::ReducedMotion => Some(("prefers-reduced-motion", "reduce")),
+            MediaEmulation::ForcedColors => Some(("forced-colors", "active")),
+            MediaEmulation::Print => None,
+        }
+    }
+}
@@ -1,4 +1,24 @@
 pub mod app_state;
+pub mod args_variant;
+pub mod browser;
+pub mod capture_options;
+pub mod comparison;
+pub mod crop;
+pub mod device;
+pub mod full_page;
+pub mod globals;
+pub mod interaction;
+pub mod mask;
+pub mod media;
 pub mod snapshot;
 pub mod snapshot_batch;
-pub mod raw_image;
\ No newline at end of file
+pub mod snapshot_batch_event;
+pub mod snapshot_batch_job;
+pub mod stability;
+pub mod stabilization;
+pub mod story;
+pub mod story_filter;
+pub mod story_tree;
+pub mod raw_image;
+pub mod readiness_strategy;
+pub mod viewport;
\ No newline at end of file
@@ -2,7 +2,10 @@ use serde::{Deserialize, Serialize};
 
 use crate::utils::save_images::safe_save_image;
 
-use super::snapshot::SnapShotType;
+use super::{
+    browser::Browser, crop::CropRect, globals::Globals, mask::MaskedRegion, snapshot::SnapShotType,
+    story::Story,
+};
 
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -12,10 +15,25 @@ pub struct RawImage {
     pub width: f64,
     pub image_type: SnapShotType,
     pub image_name: String,
+    pub viewport: Option<String>,
+    pub browser: Browser,
+    pub globals: Globals,
+    /// Physical pixels per css pixel of the capture
+    pub device_pixel_ratio: f64,
+    pub crop: Option<CropRect>,
+    pub masks: Vec<MaskedRegion>,
+    pub story: Option<Story>,
+    /// Set when consecutive captures of the story never matched
+    pub unstable: bool,
 }
 
 impl RawImage {
-    pub fn save(self, folder: &str) -> Result<String, anyhow::Error> {
-        safe_save_image(self.raw_image, folder, self.image_name.as_str())
+    pub fn save(self, assets_folder: &str, folder: &str) -> Result<String, anyhow::Error> {
+        safe_save_image(
+            self.raw_image,
+            assets_folder,
+            folder,
+            self.image_name.as_str(),
+        )
     }
 }
\ No newline at end of file
@@ -0,0 +1,160 @@
+use core::fmt;
+use std::str::FromStr;
+
+use utoipa::ToSchema;
+use validator::ValidationError;
+
+/// What to wait for before a story is considered rendered and can be captured
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReadinessStrategy {
+    /// Wait until an element matches the css selector
+    Css { selector: String },
+    /// Wait until an element matches the xpath expression
+    #[serde(rename = "xpath")]
+    XPath { selector: String },
+    /// Wait until Storybook reports that the story finished rendering
+    RenderComplete,
+    /// Wait until the page is loaded and no new resource has been fetched for a moment
+    NetworkIdle,
+    /// Wait until `document.fonts` is done loading
+    FontsLoaded,
+    /// Wait a fixed amount of time
+    Delay { ms: u64 },
+}
+
+impl Default for ReadinessStrategy {
+    fn default() -> Self {
+        ReadinessStrategy::XPath {
+            selector: "/html/body/div[5]/*".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ReadinessStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadinessStrategy::Css { selector } => write!(f, "css:{}", selector),
+            ReadinessStrategy::XPath { selector } => write!(f, "xpath:{}", selector),
+            ReadinessStrategy::RenderComplete => write!(f, "render_complete"),
+            ReadinessStrategy::NetworkIdle => write!(f, "network_idle"),
+            ReadinessStrategy::FontsLoaded => write!(f, "fonts_loaded"),
+            ReadinessStrategy::Delay { ms } => write!(f, "delay:{}", ms),
+        }
+    }
+}
+
+/// Parses the format used by the `READINESS_STRATEGY` environment variable,
+/// e.g. `css:#storybook-root > *`, `render_complete` or `delay:500`
+impl FromStr for ReadinessStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (kind, argument) = match value.split_once(':') {
+            Some((kind, argument)) => (kind, Some(argument)),
+            None => (value, None),
+        };
+
+        let strategy = match (kind, argument) {
+            ("css", Some(selector)) => ReadinessStrategy::Css {
+                selector: selector.to_string(),
+            },
+            ("xpath", Some(selector)) => ReadinessStrategy::XPath {
+                selector: selector.to_string(),
+            },
+            ("render_complete", None) => ReadinessStrategy::RenderComplete,
+            ("network_idle", None) => ReadinessStrategy::NetworkIdle,
+            ("fonts_loaded", None) => ReadinessStrategy::FontsLoaded,
+            ("delay", Some(ms)) => ReadinessStrategy::Delay { ms: ms.parse()? },
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "Unknown readiness strategy {}",
+                    value
+                )))
+            }
+        };
+
+        validate_readiness_strategy(&strategy)
+            .map_err(|_| anyhow::Error::msg(format!("Invalid readiness strategy {}", value)))?;
+
+        Ok(strategy)
+    }
+}
+
+pub fn validate_readiness_strategy(strategy: &ReadinessStrategy) -> Result<(), ValidationError> {
+    match strategy {
+        ReadinessStrategy::Css { selector } | ReadinessStrategy::XPath { selector }
+            if selector.trim().is_empty() =>
+        {
+            Err(ValidationError::new("selector must not be empty"))
+        }
+        ReadinessStrategy::Delay { ms } if *ms > 60_000 => {
+            Err(ValidationError::new("delay must be at most 60000ms"))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_readiness_strategy() {
+        assert_eq!(
+            "css:#storybook-root > *"
+                .parse::<ReadinessStrategy>()
+                .unwrap(),
+            ReadinessStrategy::Css {
+                selector: "#storybook-root > *".to_string()
+            }
+        );
+        assert_eq!(
+            "render_complete".parse::<ReadinessStrategy>().unwrap(),
+            ReadinessStrategy::RenderComplete
+        );
+        assert_eq!(
+            "delay:500".parse::<ReadinessStrategy>().unwrap(),
+            ReadinessStrategy::Delay { ms: 500 }
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_readiness_strategy() {
+        assert!("css:".parse::<ReadinessStrategy>().is_err());
+        assert!("delay:soon".parse::<ReadinessStrategy>().is_err());
+        assert!("network_idle:500".parse::<ReadinessStrategy>().is_err());
+        assert!("whenever".parse::<ReadinessStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_readiness_strategy_serde_round_trip() {
+        let strategy: ReadinessStrategy =
+            serde_json::from_str(r#"{"type":"xpath","selector":"//button"}"#).unwrap();
+
+        assert_eq!(
+            strategy,
+            ReadinessStrategy::XPath {
+                selector: "//button".to_string()
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&strategy).unwrap(),
+            r#"{"type":"xpath","selector":"//button"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ReadinessStrategy>(r#"{"type":"render_complete"}"#).unwrap(),
+            ReadinessStrategy::RenderComplete
+        );
+    }
+
+    #[test]
+    fn test_readiness_strategy_round_trip() {
+        let strategy = ReadinessStrategy::default();
+
+        assert_eq!(
+            strategy.to_string().parse::<ReadinessStrategy>().unwrap(),
+            strategy
+        );
+    }
+}
@@ -3,11 +3,15 @@ use core::fmt;
 use crate::utils::date_format;
 use chrono::NaiveDateTime;
 use sqlx::{postgres::PgRow, Row};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use super::snapshot_batch::SnapShotBatchImage;
+use super::{
+    browser::Browser, crop::CropRect, globals::Globals, mask::MaskedRegion,
+    snapshot_batch::SnapShotBatchImage, story::Story,
+};
 
-#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, sqlx::Type, Copy)]
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, sqlx::Type, Copy, ToSchema)]
 #[sqlx(type_name = "snap_shot_type", rename_all = "lowercase")]
 pub enum SnapShotType {
     New,
@@ -16,6 +20,8 @@ pub enum SnapShotType {
     LcsDiff,
     Create,
     Deleted,
+    Unstable,
+    Incomparable,
 }
 
 impl fmt::Display for SnapShotType {
@@ -33,6 +39,20 @@ pub struct SnapShot {
     pub width: f64,
     pub height: f64,
     pub snap_shot_type: SnapShotType,
+    pub viewport: Option<String>,
+    pub browser: Browser,
+    /// Storybook globals the story was rendered with
+    pub globals: Globals,
+    /// Physical pixels per css pixel of the capture
+    pub device_pixel_ratio: f64,
+    /// Area of the page the capture was cropped to
+    pub crop: Option<CropRect>,
+    /// Areas of the capture that were masked
+    pub masks: Vec<MaskedRegion>,
+    /// Story the image was captured of. Not stored for batches from before stories were
+    pub story: Option<Story>,
+    /// Why the image was not compared with its other version
+    pub reason: Option<String>,
     #[serde(with = "date_format")]
     pub created_at: NaiveDateTime,
 }
@@ -44,6 +64,13 @@ impl SnapShot {
             path: self.path.to_string(),
             width: self.width,
             height: self.height,
+            viewport: self.viewport.clone(),
+            browser: self.browser,
+            globals: self.globals.clone(),
+            device_pixel_ratio: self.device_pixel_ratio,
+            crop: self.crop,
+            masks: self.masks.clone(),
+            story: self.story.clone(),
         }
     }
 }
@@ -59,9 +86,38 @@ impl<'r> sqlx::FromRow<'r, PgRow> for SnapShot {
             "LcsDiff" => SnapShotType::LcsDiff,
             "Create" => SnapShotType::Create,
             "Deleted" => SnapShotType::Deleted,
+            "Unstable" => SnapShotType::Unstable,
+            "Incomparable" => SnapShotType::Incomparable,
             _ => SnapShotType::New,
         };
 
+        let crop = match (
+            row.try_get("crop_x")?,
+            row.try_get("crop_y")?,
+            row.try_get("crop_width")?,
+            row.try_get("crop_height")?,
+        ) {
+            (Some(x), Some(y), Some(width), Some(height)) => Some(CropRect {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => None,
+        };
+
+        let masks: sqlx::types::Json<Vec<MaskedRegion>> = row.try_get("masks")?;
+        let globals: sqlx::types::Json<Globals> = row.try_get("globals")?;
+        let story: Option<sqlx::types::Json<Story>> = row.try_get("story")?;
+
+        let browser: String = row.try_get("browser")?;
+        let browser = browser
+            .parse()
+            .map_err(|err: anyhow::Error| sqlx::Error::ColumnDecode {
+                index: "browser".to_string(),
+                source: err.into(),
+            })?;
+
         Ok(SnapShot {
             id: row.try_get("id")?,
             name: row.try_get("name")?,
@@ -71,6 +127,14 @@ impl<'r> sqlx::FromRow<'r, PgRow> for SnapShot {
             created_at: row.try_get("created_at")?,
             batch_id: row.try_get("batch_id")?,
             snap_shot_type,
+            viewport: row.try_get("viewport")?,
+            browser,
+            globals: globals.0,
+            device_pixel_ratio: row.try_get("device_pixel_ratio")?,
+            crop,
+            masks: masks.0,
+            story: story.map(|story| story.0),
+            reason: row.try_get("reason")?,
         })
     }
 }
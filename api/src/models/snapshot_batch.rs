@@ -5,7 +5,16 @@ use sqlx::{postgres::PgRow, Row};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-use super::snapshot::{SnapShot, SnapShotType};
+use super::{
+    browser::Browser,
+    crop::CropRect,
+    globals::Globals,
+    mask::MaskedRegion,
+    snapshot::{SnapShot, SnapShotType},
+    story::{Story, StoryKind},
+    story_filter::StoryFilter,
+    story_tree::{self, StoryImageChange, StoryTreeNode},
+};
 
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone, ToSchema)]
 pub struct SnapShotBatchImage {
@@ -13,6 +22,14 @@ pub struct SnapShotBatchImage {
     pub path: String,
     pub width: f64,
     pub height: f64,
+    pub viewport: Option<String>,
+    pub browser: Browser,
+    #[schema(value_type = BTreeMap<String, String>)]
+    pub globals: Globals,
+    pub device_pixel_ratio: f64,
+    pub crop: Option<CropRect>,
+    pub masks: Vec<MaskedRegion>,
+    pub story: Option<Story>,
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone, ToSchema)]
@@ -23,12 +40,68 @@ pub struct SnapShotBatch {
     pub created_at: NaiveDateTime,
     pub new_story_book_version: String,
     pub old_story_book_version: String,
+    /// Names of the viewports the stories were captured at
+    pub viewports: Vec<String>,
+    /// Browsers the stories were captured in
+    pub browsers: Vec<Browser>,
+    /// Combinations of Storybook globals the stories were captured with
+    #[schema(value_type = Vec<BTreeMap<String, String>>)]
+    pub globals: Vec<Globals>,
+    /// Stories the batch was narrowed down to, to capture the same ones again
+    pub filter: StoryFilter,
     pub created_image_paths: Vec<SnapShotBatchImage>,
     pub deleted_image_paths: Vec<SnapShotBatchImage>,
+    /// Stories that looked different every time they were captured and were not compared
+    pub unstable_image_paths: Vec<SnapShotBatchImage>,
+    /// Stories whose two captures could not be compared, such as captures at different
+    /// device pixel ratios
+    #[serde(default)]
+    pub incomparable_image_paths: Vec<IncomparableImage>,
     pub diff_image: Vec<DiffImage>,
+    /// The images above grouped by story, and the stories by title as in the Storybook sidebar
+    #[serde(default)]
+    pub stories: Vec<StoryTreeNode>,
+    /// The images of docs pages, grouped the same way as the stories
+    #[serde(default)]
+    pub docs: Vec<StoryTreeNode>,
 }
 
 impl SnapShotBatch {
+    /// Groups the images of the batch of one kind for `stories` or `docs`, listing changes
+    /// by their new image
+    pub fn story_tree(&self, kind: StoryKind) -> Vec<StoryTreeNode> {
+        let is_kind = |(image, _): &(&SnapShotBatchImage, StoryImageChange)| {
+            image.story.as_ref().is_some_and(|story| story.kind == kind)
+        };
+
+        story_tree::build_story_tree(
+            self.diff_image
+                .iter()
+                .map(|diff| (&diff.new, StoryImageChange::Changed))
+                .chain(
+                    self.created_image_paths
+                        .iter()
+                        .map(|image| (image, StoryImageChange::Created)),
+                )
+                .chain(
+                    self.deleted_image_paths
+                        .iter()
+                        .map(|image| (image, StoryImageChange::Deleted)),
+                )
+                .chain(
+                    self.unstable_image_paths
+                        .iter()
+                        .map(|image| (image, StoryImageChange::Unstable)),
+                )
+                .chain(
+                    self.incomparable_image_paths
+                        .iter()
+                        .map(|item| (&item.image, StoryImageChange::Incomparable)),
+                )
+                .filter(is_kind),
+        )
+    }
+
     pub fn into_snapshots(self) -> Vec<SnapShot> {
         let mut snapshots = Vec::new();
 
@@ -39,7 +112,15 @@ impl SnapShotBatch {
             path: item.path.clone(),
             width: item.width,
             height: item.height,
+            viewport: item.viewport.clone(),
+            browser: item.browser,
+            globals: item.globals.clone(),
+            device_pixel_ratio: item.device_pixel_ratio,
+            crop: item.crop,
+            masks: item.masks.clone(),
+            story: item.story.clone(),
             name: item.path.split('/').last().unwrap().to_string(),
+            reason: None,
             snap_shot_type: SnapShotType::Create,
         }));
 
@@ -50,10 +131,56 @@ impl SnapShotBatch {
             path: item.path.clone(),
             width: item.width,
             height: item.height,
+            viewport: item.viewport.clone(),
+            browser: item.browser,
+            globals: item.globals.clone(),
+            device_pixel_ratio: item.device_pixel_ratio,
+            crop: item.crop,
+            masks: item.masks.clone(),
+            story: item.story.clone(),
             name: item.path.split('/').last().unwrap().to_string(),
+            reason: None,
             snap_shot_type: SnapShotType::Deleted,
         }));
 
+        snapshots.extend(self.unstable_image_paths.iter().map(|item| SnapShot {
+            id: uuid::Uuid::new_v4(),
+            created_at: self.created_at,
+            batch_id: self.id,
+            path: item.path.clone(),
+            width: item.width,
+            height: item.height,
+            viewport: item.viewport.clone(),
+            browser: item.browser,
+            globals: item.globals.clone(),
+            device_pixel_ratio: item.device_pixel_ratio,
+            crop: item.crop,
+            masks: item.masks.clone(),
+            story: item.story.clone(),
+            name: item.path.split('/').next_back().unwrap().to_string(),
+            reason: None,
+            snap_shot_type: SnapShotType::Unstable,
+        }));
+
+        snapshots.extend(self.incomparable_image_paths.iter().map(|item| SnapShot {
+            id: uuid::Uuid::new_v4(),
+            created_at: self.created_at,
+            batch_id: self.id,
+            path: item.image.path.clone(),
+            width: item.image.width,
+            height: item.image.height,
+            viewport: item.image.viewport.clone(),
+            browser: item.image.browser,
+            globals: item.image.globals.clone(),
+            device_pixel_ratio: item.image.device_pixel_ratio,
+            crop: item.image.crop,
+            masks: item.image.masks.clone(),
+            story: item.image.story.clone(),
+            name: item.image.path.split('/').next_back().unwrap().to_string(),
+            reason: Some(item.reason.clone()),
+            snap_shot_type: SnapShotType::Incomparable,
+        }));
+
         snapshots.extend(
             self.diff_image
                 .iter()
@@ -65,7 +192,15 @@ impl SnapShotBatch {
                     path: item.path.clone(),
                     width: item.width,
                     height: item.height,
+                    viewport: item.viewport.clone(),
+                    browser: item.browser,
+                    globals: item.globals.clone(),
+                    device_pixel_ratio: item.device_pixel_ratio,
+                    crop: item.crop,
+                    masks: item.masks.clone(),
+                    story: item.story.clone(),
                     name: item.path.split('/').last().unwrap().to_string(),
+                    reason: None,
                     snap_shot_type: SnapShotType::ColorDiff,
                 }),
         );
@@ -80,7 +215,15 @@ impl SnapShotBatch {
                     path: item.path.clone(),
                     width: item.width,
                     height: item.height,
+                    viewport: item.viewport.clone(),
+                    browser: item.browser,
+                    globals: item.globals.clone(),
+                    device_pixel_ratio: item.device_pixel_ratio,
+                    crop: item.crop,
+                    masks: item.masks.clone(),
+                    story: item.story.clone(),
                     name: item.path.split('/').last().unwrap().to_string(),
+                    reason: None,
                     snap_shot_type: SnapShotType::LcsDiff,
                 }),
         );
@@ -96,7 +239,15 @@ impl SnapShotBatch {
                     path: item.path.clone(),
                     width: item.width,
                     height: item.height,
+                    viewport: item.viewport.clone(),
+                    browser: item.browser,
+                    globals: item.globals.clone(),
+                    device_pixel_ratio: item.device_pixel_ratio,
+                    crop: item.crop,
+                    masks: item.masks.clone(),
+                    story: item.story.clone(),
                     name: item.path.split('/').last().unwrap().to_string(),
+                    reason: None,
                     snap_shot_type: SnapShotType::New,
                 }),
         );
@@ -112,7 +263,15 @@ impl SnapShotBatch {
                     path: item.path.clone(),
                     width: item.width,
                     height: item.height,
+                    viewport: item.viewport.clone(),
+                    browser: item.browser,
+                    globals: item.globals.clone(),
+                    device_pixel_ratio: item.device_pixel_ratio,
+                    crop: item.crop,
+                    masks: item.masks.clone(),
+                    story: item.story.clone(),
                     name: item.path.split('/').last().unwrap().to_string(),
+                    reason: None,
                     snap_shot_type: SnapShotType::Old,
                 }),
         );
@@ -129,6 +288,14 @@ pub struct DiffImage {
     pub old: SnapShotBatchImage,
 }
 
+/// New image of a story that was not compared with its old one, and why
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, ToSchema)]
+pub struct IncomparableImage {
+    #[serde(flatten)]
+    pub image: SnapShotBatchImage,
+    pub reason: String,
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct SnapShotBatchDTO {
     pub id: Uuid,
@@ -137,16 +304,20 @@ pub struct SnapShotBatchDTO {
     pub created_at: NaiveDateTime,
     pub new_story_book_version: String,
     pub old_story_book_version: String,
+    pub filter: StoryFilter,
 }
 
 impl<'r> sqlx::FromRow<'r, PgRow> for SnapShotBatchDTO {
     fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let filter: sqlx::types::Json<StoryFilter> = row.try_get("filter")?;
+
         Ok(SnapShotBatchDTO {
             id: row.try_get("id")?,
             name: row.try_get("name")?,
             created_at: row.try_get("created_at")?,
             new_story_book_version: row.try_get("new_story_book_version")?,
             old_story_book_version: row.try_get("old_story_book_version")?,
+            filter: filter.0,
         })
     }
 }
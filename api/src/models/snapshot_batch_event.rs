@@ -0,0 +1,107 @@
+use utoipa::ToSchema;
+
+use super::{snapshot::SnapShotType, snapshot_batch_job::SnapShotBatchJobStatus};
+
+/// Progress of a running batch, sent to clients listening on the batch events endpoint
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SnapShotBatchEvent {
+    Captured {
+        name: String,
+        image_type: SnapShotType,
+    },
+    CaptureFailed {
+        name: String,
+        image_type: SnapShotType,
+        error: String,
+    },
+    /// The captures of a story in both versions were compared, or could not be
+    Compared {
+        name: String,
+        outcome: CompareOutcome,
+        /// Why the captures could not be compared
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Summary {
+        status: SnapShotBatchJobStatus,
+        error: Option<String>,
+        diff_found: usize,
+        created: usize,
+        deleted: usize,
+        unstable: usize,
+    },
+}
+
+/// What comparing the captures of a story found
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareOutcome {
+    Unchanged,
+    DiffFound,
+    /// The story looked different every time it was captured, so it was not compared
+    Unstable,
+    Failed,
+}
+
+impl SnapShotBatchEvent {
+    pub fn compared(name: String, outcome: CompareOutcome) -> Self {
+        SnapShotBatchEvent::Compared {
+            name,
+            outcome,
+            error: None,
+        }
+    }
+
+    pub fn compare_failed(name: String, error: String) -> Self {
+        SnapShotBatchEvent::Compared {
+            name,
+            outcome: CompareOutcome::Failed,
+            error: Some(error),
+        }
+    }
+
+    /// Name used for the `event` field of the server sent event
+    pub fn name(&self) -> &'static str {
+        match self {
+            SnapShotBatchEvent::Captured { .. } => "captured",
+            SnapShotBatchEvent::CaptureFailed { .. } => "capture_failed",
+            SnapShotBatchEvent::Compared { .. } => "compared",
+            SnapShotBatchEvent::Summary { .. } => "summary",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_compared_events() {
+        assert_eq!(
+            serde_json::to_value(SnapShotBatchEvent::compared(
+                "button--primary".to_string(),
+                CompareOutcome::DiffFound
+            ))
+            .unwrap(),
+            serde_json::json!({
+                "type": "compared",
+                "name": "button--primary",
+                "outcome": "diff_found"
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(SnapShotBatchEvent::compare_failed(
+                "button--primary".to_string(),
+                "Failed to open image".to_string()
+            ))
+            .unwrap(),
+            serde_json::json!({
+                "type": "compared",
+                "name": "button--primary",
+                "outcome": "failed",
+                "error": "Failed to open image"
+            })
+        );
+    }
+}
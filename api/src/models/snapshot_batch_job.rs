@@ -0,0 +1,61 @@
+use core::fmt;
+
+use crate::utils::date_format;
+use chrono::NaiveDateTime;
+use sqlx::{postgres::PgRow, Row};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, ToSchema)]
+pub enum SnapShotBatchJobStatus {
+    Pending,
+    Processing,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl fmt::Display for SnapShotBatchJobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, ToSchema)]
+pub struct SnapShotBatchJob {
+    pub id: Uuid,
+    pub snap_shot_batch_id: Uuid,
+    pub status: SnapShotBatchJobStatus,
+    /// Percentage of the batch that has been processed, from 0 to 100
+    pub progress: f64,
+    pub error: Option<String>,
+    #[serde(with = "date_format")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "date_format")]
+    pub updated_at: NaiveDateTime,
+}
+
+impl<'r> sqlx::FromRow<'r, PgRow> for SnapShotBatchJob {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let status_dto: String = row.try_get("status")?;
+
+        let status = match status_dto.as_str() {
+            "Pending" => SnapShotBatchJobStatus::Pending,
+            "Processing" => SnapShotBatchJobStatus::Processing,
+            "Completed" => SnapShotBatchJobStatus::Completed,
+            "Failed" => SnapShotBatchJobStatus::Failed,
+            "Cancelled" => SnapShotBatchJobStatus::Cancelled,
+            _ => SnapShotBatchJobStatus::Failed,
+        };
+
+        Ok(SnapShotBatchJob {
+            id: row.try_get("id")?,
+            snap_shot_batch_id: row.try_get("batch_id")?,
+            status,
+            progress: row.try_get("progress")?,
+            error: row.try_get("error")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
@@ -0,0 +1,10 @@
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// Captures a story again until two consecutive screenshots are identical
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema, Validate)]
+pub struct Stability {
+    /// Captures taken at most before the story is flagged as unstable
+    #[validate(range(min = 2, max = 10))]
+    pub max_attempts: u32,
+}
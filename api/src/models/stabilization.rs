@@ -0,0 +1,49 @@
+use utoipa::ToSchema;
+
+/// Takes out what changes from one render of a story to the next before it is captured.
+/// Every switch is off unless turned on, so stories render as they did before.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Default, PartialEq, ToSchema)]
+#[serde(default)]
+pub struct Stabilization {
+    /// Turns off css animations and transitions
+    pub disable_animations: bool,
+    /// Hides the blinking text caret of focused inputs
+    pub hide_caret: bool,
+    /// Pauses videos and freezes GIFs on the frame they are showing
+    pub pause_media: bool,
+    /// Pins `Date` to a fixed point in time
+    pub freeze_time: bool,
+    /// Makes `Math.random` return the same sequence on every render
+    pub seed_random: bool,
+}
+
+impl Stabilization {
+    pub fn is_enabled(&self) -> bool {
+        self.disable_animations
+            || self.hide_caret
+            || self.pause_media
+            || self.freeze_time
+            || self.seed_random
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_stabilization_defaults_to_disabled() {
+        let stabilization: Stabilization = serde_json::from_str("{}").unwrap();
+
+        assert_eq!(stabilization, Stabilization::default());
+        assert!(!stabilization.is_enabled());
+
+        let stabilization: Stabilization =
+            serde_json::from_str(r#"{ "freeze_time": true }"#).unwrap();
+
+        assert!(stabilization.freeze_time);
+        assert!(!stabilization.disable_animations);
+        assert!(!stabilization.seed_random);
+        assert!(stabilization.is_enabled());
+    }
+}
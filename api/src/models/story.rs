@@ -0,0 +1,41 @@
+use utoipa::ToSchema;
+
+/// The story an image was captured of, as listed in the Storybook index
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema)]
+pub struct Story {
+    pub id: String,
+    /// Where the story sits in the sidebar, e.g. `Components/Forms/Input`
+    pub title: String,
+    /// Display name of the story, e.g. `Default`
+    pub name: String,
+    /// File the story is written in, e.g. `./src/Input.stories.tsx`. Not given before Storybook 6.4
+    pub import_path: Option<String>,
+    pub tags: Vec<String>,
+    /// Whether the image is of a story or of a docs page. Stories stored before docs pages
+    /// were captured are stories
+    #[serde(default)]
+    pub kind: StoryKind,
+}
+
+/// Kind of an entry of the Storybook index that is captured
+#[derive(
+    Debug, serde::Deserialize, serde::Serialize, Clone, Copy, Default, PartialEq, Eq, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum StoryKind {
+    #[default]
+    Story,
+    /// An MDX or autodocs page, rendered with `viewMode=docs`
+    Docs,
+}
+
+impl Story {
+    /// The groups of the title, from the outermost to the component
+    pub fn title_path(&self) -> Vec<&str> {
+        self.title
+            .split('/')
+            .map(|group| group.trim())
+            .filter(|group| !group.is_empty())
+            .collect()
+    }
+}
@@ -0,0 +1,126 @@
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::utils::glob::matches_glob;
+
+/// Narrows a batch down to some of the stories, e.g. those a change touches. Applied to
+/// the Storybook index before anything is captured and stored with the batch, so that the
+/// same stories can be captured again
+#[derive(
+    Debug, serde::Deserialize, serde::Serialize, Clone, Default, PartialEq, ToSchema, Validate,
+)]
+pub struct StoryFilter {
+    /// Stories whose id or title matches one of these, where `*` matches anything,
+    /// e.g. `button--*` or `Components/Button*`. Includes every story when empty
+    #[serde(default)]
+    #[validate(custom(function = "validate_patterns"))]
+    pub include: Vec<String>,
+    /// Stories left out even when included, matched the same way
+    #[serde(default)]
+    #[validate(custom(function = "validate_patterns"))]
+    pub exclude: Vec<String>,
+    /// Storybook tags, of which a story needs at least one. Tags starting with `!` leave
+    /// out the stories that have them, e.g. `["visual", "!no-visual-test"]`
+    #[serde(default)]
+    #[validate(custom(function = "validate_patterns"))]
+    pub tags: Vec<String>,
+}
+
+impl StoryFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.tags.is_empty()
+    }
+
+    pub fn matches(&self, id: &str, title: &str, tags: &[String]) -> bool {
+        let matches_story =
+            |pattern: &String| matches_glob(pattern, id) || matches_glob(pattern, title);
+
+        let has_tag = |tag: &str| tags.iter().any(|story_tag| story_tag == tag);
+
+        let excluded_tags: Vec<&str> = self
+            .tags
+            .iter()
+            .filter_map(|tag| tag.strip_prefix('!'))
+            .collect();
+        let required_tags: Vec<&str> = self
+            .tags
+            .iter()
+            .filter(|tag| !tag.starts_with('!'))
+            .map(|tag| tag.as_str())
+            .collect();
+
+        (self.include.is_empty() || self.include.iter().any(matches_story))
+            && !self.exclude.iter().any(matches_story)
+            && (required_tags.is_empty() || required_tags.into_iter().any(has_tag))
+            && !excluded_tags.into_iter().any(has_tag)
+    }
+}
+
+fn validate_patterns(patterns: &[String]) -> Result<(), ValidationError> {
+    if patterns
+        .iter()
+        .all(|pattern| !pattern.trim_start_matches('!').trim().is_empty())
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("patterns must not be empty"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn test_story_filter_matches() {
+        let filter = StoryFilter {
+            include: tags(&["button--*", "Forms/*"]),
+            exclude: tags(&["*--deprecated"]),
+            tags: vec![],
+        };
+
+        assert!(filter.matches("button--primary", "Button", &[]));
+        assert!(filter.matches("input--default", "Forms/Input", &[]));
+        assert!(!filter.matches("button--deprecated", "Button", &[]));
+        assert!(!filter.matches("card--default", "Card", &[]));
+        assert!(StoryFilter::default().matches("card--default", "Card", &[]));
+    }
+
+    #[test]
+    fn test_story_filter_matches_tags() {
+        let filter = StoryFilter {
+            tags: tags(&["visual", "!no-visual-test"]),
+            ..Default::default()
+        };
+
+        assert!(filter.matches("button--primary", "Button", &tags(&["dev", "visual"])));
+        assert!(!filter.matches("button--primary", "Button", &tags(&["dev"])));
+        assert!(!filter.matches(
+            "button--primary",
+            "Button",
+            &tags(&["visual", "no-visual-test"])
+        ));
+
+        let filter = StoryFilter {
+            tags: tags(&["!no-visual-test"]),
+            ..Default::default()
+        };
+
+        assert!(filter.matches("button--primary", "Button", &[]));
+    }
+
+    #[test]
+    fn test_validate_story_filter() {
+        let filter = StoryFilter {
+            tags: tags(&["!"]),
+            ..Default::default()
+        };
+
+        assert!(filter.validate().is_err());
+        assert!(StoryFilter::default().is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+use utoipa::ToSchema;
+
+use super::{snapshot_batch::SnapShotBatchImage, story::Story};
+
+/// What a batch found for an image of a story
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StoryImageChange {
+    Changed,
+    Created,
+    Deleted,
+    Unstable,
+    Incomparable,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema)]
+pub struct StoryImage {
+    /// Name of the image in the lists of the batch
+    pub name: String,
+    pub change: StoryImageChange,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema)]
+pub struct StoryTreeStory {
+    pub story: Story,
+    pub images: Vec<StoryImage>,
+}
+
+/// A group of the Storybook sidebar, such as `Components` or `Forms`, with the groups and
+/// stories below it
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema)]
+pub struct StoryTreeNode {
+    pub name: String,
+    /// Title of the group, e.g. `Components/Forms`
+    pub title: String,
+    pub children: Vec<StoryTreeNode>,
+    /// Stories whose title ends at this group
+    pub stories: Vec<StoryTreeStory>,
+}
+
+/// Groups images by their story and the stories by their title, sorted by name the way
+/// Storybook sorts its sidebar. Images of batches captured before stories were stored
+/// have no story and are left out
+pub fn build_story_tree<'a>(
+    images: impl IntoIterator<Item = (&'a SnapShotBatchImage, StoryImageChange)>,
+) -> Vec<StoryTreeNode> {
+    let mut roots: Vec<StoryTreeNode> = vec![];
+
+    for (image, change) in images {
+        if let Some(story) = &image.story {
+            let image = StoryImage {
+                name: image.name.clone(),
+                change,
+            };
+
+            insert(&mut roots, "", &story.title_path(), story, image);
+        }
+    }
+
+    sort(&mut roots);
+
+    roots
+}
+
+fn insert(
+    nodes: &mut Vec<StoryTreeNode>,
+    parent_title: &str,
+    groups: &[&str],
+    story: &Story,
+    image: StoryImage,
+) {
+    let Some((group, groups)) = groups.split_first() else {
+        return;
+    };
+
+    let title = if parent_title.is_empty() {
+        group.to_string()
+    } else {
+        format!("{}/{}", parent_title, group)
+    };
+
+    let index = match nodes.iter().position(|node| node.name == *group) {
+        Some(index) => index,
+        None => {
+            nodes.push(StoryTreeNode {
+                name: group.to_string(),
+                title: title.clone(),
+                children: vec![],
+                stories: vec![],
+            });
+            nodes.len() - 1
+        }
+    };
+    let node = &mut nodes[index];
+
+    if !groups.is_empty() {
+        insert(&mut node.children, &title, groups, story, image);
+    } else if let Some(other) = node
+        .stories
+        .iter_mut()
+        .find(|other| other.story.id == story.id)
+    {
+        other.images.push(image);
+    } else {
+        node.stories.push(StoryTreeStory {
+            story: story.clone(),
+            images: vec![image],
+        });
+    }
+}
+
+fn sort(nodes: &mut [StoryTreeNode]) {
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for node in nodes {
+        node.stories.sort_by(|a, b| a.story.name.cmp(&b.story.name));
+
+        for story in node.stories.iter_mut() {
+            story.images.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        sort(&mut node.children);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{browser::Browser, globals::Globals, story::StoryKind};
+
+    fn image(name: &str, story_id: &str, title: &str) -> SnapShotBatchImage {
+        SnapShotBatchImage {
+            name: name.to_string(),
+            path: format!("assets/{}.png", name),
+            width: 20.0,
+            height: 10.0,
+            viewport: None,
+            browser: Browser::Chrome,
+            globals: Globals::new(),
+            device_pixel_ratio: 1.0,
+            crop: None,
+            masks: vec![],
+            story: Some(Story {
+                id: story_id.to_string(),
+                title: title.to_string(),
+                name: story_id.split("--").last().unwrap().to_string(),
+                import_path: None,
+                tags: vec![],
+                kind: StoryKind::Story,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_build_story_tree() {
+        let input_dark = image(
+            "input--default$dark",
+            "input--default",
+            "Components/Forms/Input",
+        );
+        let input = image("input--default", "input--default", "Components/Forms/Input");
+        let button = image("button--primary", "button--primary", "Components/Button");
+        let intro = image("intro--page", "intro--page", "Intro");
+        let mut legacy = image("card--default", "card--default", "Card");
+        legacy.story = None;
+
+        let tree = build_story_tree([
+            (&input_dark, StoryImageChange::Changed),
+            (&intro, StoryImageChange::Created),
+            (&button, StoryImageChange::Deleted),
+            (&input, StoryImageChange::Unstable),
+            (&legacy, StoryImageChange::Changed),
+        ]);
+
+        let names: Vec<&str> = tree.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, vec!["Components", "Intro"]);
+
+        let components = &tree[0];
+        assert!(components.stories.is_empty());
+        assert_eq!(components.children[0].title, "Components/Button");
+        assert_eq!(components.children[1].name, "Forms");
+
+        let input = &components.children[1].children[0];
+        assert_eq!(input.title, "Components/Forms/Input");
+        assert_eq!(input.stories.len(), 1);
+        assert_eq!(
+            input.stories[0].images,
+            vec![
+                StoryImage {
+                    name: "input--default".to_string(),
+                    change: StoryImageChange::Unstable,
+                },
+                StoryImage {
+                    name: "input--default$dark".to_string(),
+                    change: StoryImageChange::Changed,
+                },
+            ]
+        );
+    }
+}
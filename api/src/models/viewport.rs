@@ -0,0 +1,17 @@
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::utils::file_safe_name::validate_file_safe_name;
+
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, ToSchema, Validate)]
+pub struct Viewport {
+    #[validate(
+        length(min = 1, max = 50),
+        custom(function = "validate_file_safe_name")
+    )]
+    pub name: String,
+    #[validate(range(min = 1, max = 10000))]
+    pub width: u32,
+    #[validate(range(min = 1, max = 10000))]
+    pub height: u32,
+}
@@ -1,2 +1,3 @@
 pub mod snapshot_history_service;
+pub mod snapshot_job_service;
 pub mod snapshot_service;
@@ -1,15 +1,21 @@
 use crate::{
     db::snapshot_store,
-    models::snapshot_batch::{DiffImage, SnapShotBatch},
+    models::snapshot_batch::{DiffImage, IncomparableImage, SnapShotBatch},
 };
 use anyhow::Error;
 use uuid::Uuid;
 
 use crate::{
-    db::{snapshot_batch_store, snapshot_store::get_all_snapshots_by_batch_id},
+    db::{
+        snapshot_batch_job_store, snapshot_batch_store,
+        snapshot_store::get_all_snapshots_by_batch_id,
+    },
     models::{
+        browser::Browser,
+        globals::Globals,
         snapshot::{SnapShot, SnapShotType},
         snapshot_batch::SnapShotBatchDTO,
+        story::StoryKind,
     },
 };
 
@@ -55,7 +61,11 @@ pub async fn delete_snapshot_batch_by_id(
     let snapshots_deletion =
         snapshot_store::delete_all_snapshots_by_batch_id(&mut transaction, &id).await?;
 
-    if snapshots_deletion.is_none() || batch_deletion.is_none() {
+    snapshot_batch_job_store::delete_snap_shot_batch_jobs_by_batch_id(&mut transaction, &id)
+        .await?;
+
+    // Batches whose job failed have no snap shots, so only the batch itself must exist
+    if batch_deletion.is_none() {
         transaction.rollback().await?;
         tracing::error!("Cannot delete snap shot batch by id: {}. Wrong ID", id);
         return Ok(None);
@@ -64,7 +74,7 @@ pub async fn delete_snapshot_batch_by_id(
 
     Ok(Some(create_snapshot_batch_from_dto(
         batch_deletion.unwrap(),
-        snapshots_deletion.unwrap(),
+        snapshots_deletion.unwrap_or_default(),
     )))
 }
 
@@ -90,12 +100,31 @@ fn create_snapshot_batch_from_dto(
         .filter(|item| item.snap_shot_type == SnapShotType::LcsDiff)
         .collect();
 
-    SnapShotBatch {
+    let mut viewports: Vec<String> = snapshots
+        .iter()
+        .filter_map(|snap| snap.viewport.clone())
+        .collect();
+    viewports.sort();
+    viewports.dedup();
+
+    let mut browsers: Vec<Browser> = snapshots.iter().map(|snap| snap.browser).collect();
+    browsers.sort();
+    browsers.dedup();
+
+    let mut globals: Vec<Globals> = snapshots.iter().map(|snap| snap.globals.clone()).collect();
+    globals.sort();
+    globals.dedup();
+
+    let mut batch = SnapShotBatch {
         id: snap_shot_batch_dto.id,
         name: snap_shot_batch_dto.name,
         created_at: snap_shot_batch_dto.created_at,
         new_story_book_version: snap_shot_batch_dto.new_story_book_version,
         old_story_book_version: snap_shot_batch_dto.old_story_book_version,
+        viewports,
+        browsers,
+        globals,
+        filter: snap_shot_batch_dto.filter,
         diff_image: snapshots
             .clone()
             .into_iter()
@@ -156,7 +185,26 @@ fn create_snapshot_batch_from_dto(
                 return None;
             })
             .collect(),
-    }
+        unstable_image_paths: snapshots
+            .iter()
+            .filter(|snap| snap.snap_shot_type == SnapShotType::Unstable)
+            .map(|snap| snap.into_snapshot_batch_image())
+            .collect(),
+        incomparable_image_paths: snapshots
+            .iter()
+            .filter(|snap| snap.snap_shot_type == SnapShotType::Incomparable)
+            .map(|snap| IncomparableImage {
+                image: snap.into_snapshot_batch_image(),
+                reason: snap.reason.clone().unwrap_or_default(),
+            })
+            .collect(),
+        stories: vec![],
+        docs: vec![],
+    };
+    batch.stories = batch.story_tree(StoryKind::Story);
+    batch.docs = batch.story_tree(StoryKind::Docs);
+
+    batch
 }
 
 pub async fn delete_all_batches(db_pool: sqlx::Pool<sqlx::Postgres>) -> Result<(), anyhow::Error> {
@@ -0,0 +1,226 @@
+use anyhow::Error;
+use chrono::Utc;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::{
+    db::{snapshot_batch_job_store, snapshot_batch_store},
+    models::{
+        comparison::SnapShotBatchRequest,
+        snapshot_batch::SnapShotBatchDTO,
+        snapshot_batch_event::SnapShotBatchEvent,
+        snapshot_batch_job::{SnapShotBatchJob, SnapShotBatchJobStatus},
+    },
+    service::{snapshot_history_service, snapshot_service},
+    utils::{
+        batch_cancellation::SnapShotBatchCancellations,
+        batch_events::{self, SnapShotBatchEventSender, SnapShotBatchEvents},
+        env_variables::EnvVariables,
+        session_pool::SessionPool,
+    },
+};
+
+/// Creates the batch and its job, then runs the capture in the background.
+/// Returns as soon as the job is persisted so callers can poll for its status.
+pub async fn create_snapshot_batch_job(
+    request: SnapShotBatchRequest,
+    db_pool: &sqlx::Pool<sqlx::Postgres>,
+    batch_events: &SnapShotBatchEvents,
+    batch_cancellations: &SnapShotBatchCancellations,
+    session_pool: &SessionPool,
+    env_variables: &EnvVariables,
+) -> Result<SnapShotBatchJob, Error> {
+    let mut transaction: sqlx::Transaction<'_, sqlx::Postgres> = db_pool.begin().await?;
+
+    let batch = snapshot_batch_store::insert_snap_shot_batch(
+        &mut transaction,
+        &SnapShotBatchDTO {
+            id: Uuid::new_v4(),
+            created_at: Utc::now().naive_utc(),
+            name: request.comparison.name(),
+            new_story_book_version: request.comparison.new_label(),
+            old_story_book_version: request.comparison.old_label(),
+            filter: request.options.filter.clone(),
+        },
+    )
+    .await?;
+
+    let job =
+        snapshot_batch_job_store::insert_snap_shot_batch_job(&mut transaction, &batch.id).await?;
+
+    transaction.commit().await?;
+
+    // Open the channel before spawning so that no event is missed by early listeners
+    let events = batch_events.open(batch.id);
+    let cancellation = batch_cancellations.open(batch.id);
+
+    tokio::spawn(run_snapshot_batch_job(
+        job.id,
+        batch,
+        request,
+        db_pool.clone(),
+        RunningBatch {
+            events,
+            cancellation,
+            batch_events: batch_events.clone(),
+            batch_cancellations: batch_cancellations.clone(),
+            session_pool: session_pool.clone(),
+            env_variables: env_variables.clone(),
+        },
+    ));
+
+    Ok(job)
+}
+
+pub async fn get_snapshot_batch_job_by_id(
+    id: Uuid,
+    db_pool: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<Option<SnapShotBatchJob>, Error> {
+    snapshot_batch_job_store::get_snap_shot_batch_job_by_id(db_pool, &id).await
+}
+
+/// Builds the summary of a batch that is no longer running from what was stored
+pub async fn get_snapshot_batch_summary(
+    batch_id: Uuid,
+    db_pool: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<Option<SnapShotBatchEvent>, Error> {
+    let job =
+        match snapshot_batch_job_store::get_snap_shot_batch_job_by_batch_id(db_pool, &batch_id)
+            .await?
+        {
+            Some(job) => job,
+            None => return Ok(None),
+        };
+
+    let batch = snapshot_history_service::get_snap_shot_batch_by_id(batch_id, db_pool).await?;
+
+    Ok(Some(SnapShotBatchEvent::Summary {
+        status: job.status,
+        error: job.error,
+        diff_found: batch.as_ref().map_or(0, |batch| batch.diff_image.len()),
+        created: batch
+            .as_ref()
+            .map_or(0, |batch| batch.created_image_paths.len()),
+        deleted: batch
+            .as_ref()
+            .map_or(0, |batch| batch.deleted_image_paths.len()),
+        unstable: batch
+            .as_ref()
+            .map_or(0, |batch| batch.unstable_image_paths.len()),
+    }))
+}
+
+/// Asks the running batch to stop. The job is marked as cancelled once its
+/// captures and comparisons have wound down. Returns `None` when the batch has no job.
+pub async fn cancel_snapshot_batch(
+    batch_id: Uuid,
+    db_pool: &sqlx::Pool<sqlx::Postgres>,
+    batch_cancellations: &SnapShotBatchCancellations,
+) -> Result<Option<SnapShotBatchJob>, Error> {
+    if batch_cancellations.cancel(&batch_id) {
+        tracing::info!("Cancelling snap shot batch {}", batch_id);
+    }
+
+    snapshot_batch_job_store::get_snap_shot_batch_job_by_batch_id(db_pool, &batch_id).await
+}
+
+pub async fn fail_unfinished_jobs(db_pool: &sqlx::Pool<sqlx::Postgres>) -> Result<(), Error> {
+    let jobs = snapshot_batch_job_store::fail_unfinished_snap_shot_batch_jobs(db_pool).await?;
+
+    for job in jobs {
+        tracing::warn!("Marked unfinished snap shot batch job {} as failed", job.id);
+    }
+
+    Ok(())
+}
+
+/// Channels a background job reports on and is stopped through,
+/// and the sessions and settings it captures with
+struct RunningBatch {
+    events: SnapShotBatchEventSender,
+    cancellation: CancellationToken,
+    session_pool: SessionPool,
+    env_variables: EnvVariables,
+    batch_events: SnapShotBatchEvents,
+    batch_cancellations: SnapShotBatchCancellations,
+}
+
+impl RunningBatch {
+    fn close(&self, batch_id: &Uuid) {
+        self.batch_events.close(batch_id);
+        self.batch_cancellations.close(batch_id);
+    }
+}
+
+async fn run_snapshot_batch_job(
+    job_id: Uuid,
+    batch: SnapShotBatchDTO,
+    request: SnapShotBatchRequest,
+    db_pool: sqlx::Pool<sqlx::Postgres>,
+    running_batch: RunningBatch,
+) {
+    let batch_id = batch.id;
+
+    if snapshot_batch_job_store::update_snap_shot_batch_job_status(
+        &db_pool,
+        &job_id,
+        SnapShotBatchJobStatus::Processing,
+        None,
+    )
+    .await
+    .is_err()
+    {
+        running_batch.close(&batch_id);
+        return;
+    }
+
+    let (status, error, batch) = match snapshot_service::create_snapshots(
+        &job_id,
+        batch,
+        &request,
+        &db_pool,
+        &running_batch.events,
+        &running_batch.cancellation,
+        &running_batch.session_pool,
+        &running_batch.env_variables,
+    )
+    .await
+    {
+        Ok(batch) => (SnapShotBatchJobStatus::Completed, None, Some(batch)),
+        Err(_) if running_batch.cancellation.is_cancelled() => {
+            tracing::info!("Snap shot batch job {} was cancelled", job_id);
+            (SnapShotBatchJobStatus::Cancelled, None, None)
+        }
+        Err(err) => {
+            tracing::error!("Snap shot batch job {} failed: {}", job_id, err);
+            (SnapShotBatchJobStatus::Failed, Some(err.to_string()), None)
+        }
+    };
+
+    let _ = snapshot_batch_job_store::update_snap_shot_batch_job_status(
+        &db_pool,
+        &job_id,
+        status,
+        error.clone(),
+    )
+    .await;
+
+    batch_events::emit(
+        &running_batch.events,
+        SnapShotBatchEvent::Summary {
+            status,
+            error,
+            diff_found: batch.as_ref().map_or(0, |batch| batch.diff_image.len()),
+            created: batch
+                .as_ref()
+                .map_or(0, |batch| batch.created_image_paths.len()),
+            deleted: batch
+                .as_ref()
+                .map_or(0, |batch| batch.deleted_image_paths.len()),
+            unstable: batch
+                .as_ref()
+                .map_or(0, |batch| batch.unstable_image_paths.len()),
+        },
+    );
+    running_batch.close(&batch_id);
+}
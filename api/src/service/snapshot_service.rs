@@ -2,23 +2,29 @@ use std::fs;
 
 use crate::{
     models::{
+        capture_options::CaptureOptions,
+        comparison::{CaptureTarget, SnapShotBatchRequest},
+        globals,
         raw_image::RawImage,
-        snapshot_batch::{DiffImage, SnapShotBatch, SnapShotBatchImage},
+        snapshot_batch::{DiffImage, IncomparableImage, SnapShotBatch, SnapShotBatchImage},
+        story::StoryKind,
     },
     utils::{
-        capture_screenshots::{self},
+        batch_events::SnapShotBatchEventSender,
+        capture_screenshots::{self, CaptureSettings},
         compare_images::{self},
-        env_variables,
+        env_variables::EnvVariables,
+        session_pool::SessionPool,
         story_book::get_screenshot_params_by_url,
     },
 };
 use anyhow::Error;
-use chrono::Utc;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 use crate::{
     db::{
-        snapshot_batch_store,
+        snapshot_batch_job_store,
         snapshot_store::{self},
     },
     models::{
@@ -27,29 +33,23 @@ use crate::{
     },
 };
 
+#[allow(clippy::too_many_arguments)]
 pub async fn create_snapshots(
-    new_url: &str,
-    old_url: &str,
+    job_id: &Uuid,
+    batch: SnapShotBatchDTO,
+    request: &SnapShotBatchRequest,
     db_pool: &sqlx::Pool<sqlx::Postgres>,
+    events: &SnapShotBatchEventSender,
+    cancellation: &CancellationToken,
+    session_pool: &SessionPool,
+    env_variables: &EnvVariables,
 ) -> Result<SnapShotBatch, Error> {
-    let asset_folder = env_variables::EnvVariables::new().assets_folder;
-
-    let new_url = new_url.to_string();
-    let old_url = old_url.to_string();
-
-    let mut transaction: sqlx::Transaction<'_, sqlx::Postgres> = db_pool.begin().await?;
-
-    let batch = snapshot_batch_store::insert_snap_shot_batch(
-        &mut transaction,
-        &SnapShotBatchDTO {
-            id: Uuid::new_v4(),
-            created_at: Utc::now().naive_utc(),
-            name: format!("{}-{}", new_url, old_url),
-            new_story_book_version: new_url.clone().to_string(),
-            old_story_book_version: old_url.clone().to_string(),
-        },
-    )
-    .await?;
+    let SnapShotBatchRequest {
+        comparison,
+        options,
+    } = request;
+    let asset_folder = env_variables.assets_folder.clone();
+    let settings = CaptureSettings::new(options, env_variables);
 
     let random_folder_name = format!(
         "{}-{}",
@@ -60,11 +60,32 @@ pub async fn create_snapshots(
         &batch.id.clone(),
     );
 
-    let images_1: Vec<Result<RawImage, Error>> =
-        handle_snap_shot_for_url(&new_url, SnapShotType::New).await?;
+    let images_1: Vec<Result<RawImage, Error>> = handle_snap_shot_for_url(
+        &comparison.new_target(),
+        SnapShotType::New,
+        options,
+        &settings,
+        session_pool,
+        events,
+        cancellation,
+    )
+    .await?;
+
+    report_progress(db_pool, job_id, 40.0).await;
+
+    let images_2: Vec<Result<RawImage, Error>> = handle_snap_shot_for_url(
+        &comparison.old_target(),
+        SnapShotType::Old,
+        options,
+        &settings,
+        session_pool,
+        events,
+        cancellation,
+    )
+    .await?;
+
+    report_progress(db_pool, job_id, 80.0).await;
 
-    let images_2: Vec<Result<RawImage, Error>> =
-        handle_snap_shot_for_url(&old_url, SnapShotType::Old).await?;
     /*
        Remove images that are not valid from each list
        If an image in array 1 is not valid, remove it
@@ -93,25 +114,44 @@ pub async fn create_snapshots(
             }
         });
 
-    let diff_images: compare_images::CompareImagesReturn =
-        compare_images::compare_images(images_before_cleaned.clone(), images_after_cleaned.clone())
-            .await?;
+    let diff_images: compare_images::CompareImagesReturn = compare_images::compare_images(
+        images_before_cleaned.clone(),
+        images_after_cleaned.clone(),
+        events,
+        cancellation,
+    )
+    .await?;
+
+    report_progress(db_pool, job_id, 90.0).await;
 
-    create_folders(format!("{}/{}", asset_folder, random_folder_name).as_str())?;
+    let batch_folder = format!("{}/{}", asset_folder, random_folder_name);
 
-    let batch = SnapShotBatch {
+    create_folders(batch_folder.as_str())?;
+
+    let mut batch = SnapShotBatch {
         id: batch.id,
         name: batch.name,
         created_at: batch.created_at,
         new_story_book_version: batch.new_story_book_version,
         old_story_book_version: batch.old_story_book_version,
+        viewports: options
+            .viewports
+            .iter()
+            .map(|viewport| viewport.name.clone())
+            .collect(),
+        browsers: comparison.browsers(options),
+        globals: globals::combinations(&options.globals),
+        filter: batch.filter,
         created_image_paths: diff_images
             .created_images_paths
             .into_iter()
             .map(|img| {
                 let path = img
                     .clone()
-                    .save(format!("{}/created", &random_folder_name).as_str())
+                    .save(
+                        &asset_folder,
+                        format!("{}/created", &random_folder_name).as_str(),
+                    )
                     .unwrap();
 
                 SnapShotBatchImage {
@@ -119,6 +159,13 @@ pub async fn create_snapshots(
                     path: path,
                     width: img.width,
                     height: img.height,
+                    viewport: img.viewport,
+                    browser: img.browser,
+                    globals: img.globals,
+                    device_pixel_ratio: img.device_pixel_ratio,
+                    crop: img.crop,
+                    masks: img.masks,
+                    story: img.story,
                 }
             })
             .collect(),
@@ -128,7 +175,37 @@ pub async fn create_snapshots(
             .map(|img| {
                 let path = img
                     .clone()
-                    .save(format!("{}/deleted", &random_folder_name).as_str())
+                    .save(
+                        &asset_folder,
+                        format!("{}/deleted", &random_folder_name).as_str(),
+                    )
+                    .unwrap();
+
+                SnapShotBatchImage {
+                    name: img.image_name,
+                    path,
+                    width: img.width,
+                    height: img.height,
+                    viewport: img.viewport,
+                    browser: img.browser,
+                    globals: img.globals,
+                    device_pixel_ratio: img.device_pixel_ratio,
+                    crop: img.crop,
+                    masks: img.masks,
+                    story: img.story,
+                }
+            })
+            .collect(),
+        unstable_image_paths: diff_images
+            .unstable_images_paths
+            .into_iter()
+            .map(|img| {
+                let path = img
+                    .clone()
+                    .save(
+                        &asset_folder,
+                        format!("{}/unstable", &random_folder_name).as_str(),
+                    )
                     .unwrap();
 
                 SnapShotBatchImage {
@@ -136,6 +213,43 @@ pub async fn create_snapshots(
                     path,
                     width: img.width,
                     height: img.height,
+                    viewport: img.viewport,
+                    browser: img.browser,
+                    globals: img.globals,
+                    device_pixel_ratio: img.device_pixel_ratio,
+                    crop: img.crop,
+                    masks: img.masks,
+                    story: img.story,
+                }
+            })
+            .collect(),
+        incomparable_image_paths: diff_images
+            .incomparable_images_paths
+            .into_iter()
+            .map(|(img, reason)| {
+                let path = img
+                    .clone()
+                    .save(
+                        &asset_folder,
+                        format!("{}/incomparable", &random_folder_name).as_str(),
+                    )
+                    .unwrap();
+
+                IncomparableImage {
+                    image: SnapShotBatchImage {
+                        name: img.image_name,
+                        path,
+                        width: img.width,
+                        height: img.height,
+                        viewport: img.viewport,
+                        browser: img.browser,
+                        globals: img.globals,
+                        device_pixel_ratio: img.device_pixel_ratio,
+                        crop: img.crop,
+                        masks: img.masks,
+                        story: img.story,
+                    },
+                    reason,
                 }
             })
             .collect(),
@@ -160,21 +274,33 @@ pub async fn create_snapshots(
 
                 let new_image_path = new_image
                     .clone()
-                    .save(format!("{}/new", random_folder_name).as_str())
+                    .save(
+                        &asset_folder,
+                        format!("{}/new", random_folder_name).as_str(),
+                    )
                     .unwrap();
                 let old_image_path = old_image
                     .clone()
-                    .save(format!("{}/old", random_folder_name).as_str())
+                    .save(
+                        &asset_folder,
+                        format!("{}/old", random_folder_name).as_str(),
+                    )
                     .unwrap();
 
                 let color_diff_path = color_image
                     .clone()
-                    .save(format!("{}/diff/color", random_folder_name).as_str())
+                    .save(
+                        &asset_folder,
+                        format!("{}/diff/color", random_folder_name).as_str(),
+                    )
                     .unwrap();
 
                 let lcs_diff_path = lcs_image
                     .clone()
-                    .save(format!("{}/diff/lcs", random_folder_name).as_str())
+                    .save(
+                        &asset_folder,
+                        format!("{}/diff/lcs", random_folder_name).as_str(),
+                    )
                     .unwrap();
 
                 Some(DiffImage {
@@ -183,29 +309,61 @@ pub async fn create_snapshots(
                         width: new_image.width,
                         height: new_image.height,
                         path: new_image_path,
+                        viewport: new_image.viewport,
+                        browser: new_image.browser,
+                        globals: new_image.globals,
+                        device_pixel_ratio: new_image.device_pixel_ratio,
+                        crop: new_image.crop,
+                        masks: new_image.masks,
+                        story: new_image.story,
                     },
                     old: SnapShotBatchImage {
                         name: old_image.image_name,
                         width: old_image.width,
                         height: old_image.height,
                         path: old_image_path,
+                        viewport: old_image.viewport,
+                        browser: old_image.browser,
+                        globals: old_image.globals,
+                        device_pixel_ratio: old_image.device_pixel_ratio,
+                        crop: old_image.crop,
+                        masks: old_image.masks,
+                        story: old_image.story,
                     },
                     color_diff: SnapShotBatchImage {
                         name: image_name,
                         width: color_image.width,
                         height: color_image.height,
                         path: color_diff_path,
+                        viewport: color_image.viewport,
+                        browser: color_image.browser,
+                        globals: color_image.globals,
+                        device_pixel_ratio: color_image.device_pixel_ratio,
+                        crop: color_image.crop,
+                        masks: color_image.masks,
+                        story: color_image.story,
                     },
                     lcs_diff: SnapShotBatchImage {
                         name: lcs_image.image_name,
                         width: lcs_image.width,
                         height: lcs_image.height,
                         path: lcs_diff_path,
+                        viewport: lcs_image.viewport,
+                        browser: lcs_image.browser,
+                        globals: lcs_image.globals,
+                        device_pixel_ratio: lcs_image.device_pixel_ratio,
+                        crop: lcs_image.crop,
+                        masks: lcs_image.masks,
+                        story: lcs_image.story,
                     },
                 })
             })
             .collect(),
+        stories: vec![],
+        docs: vec![],
     };
+    batch.stories = batch.story_tree(StoryKind::Story);
+    batch.docs = batch.story_tree(StoryKind::Docs);
 
     let snap_shot_array = batch
         .clone()
@@ -217,6 +375,14 @@ pub async fn create_snapshots(
         })
         .collect::<Vec<SnapShot>>();
 
+    // Saving can't be interrupted, so drop whatever was written if the batch got cancelled meanwhile
+    if cancellation.is_cancelled() {
+        fs::remove_dir_all(&batch_folder)?;
+        return Err(Error::msg("Snap shot batch was cancelled"));
+    }
+
+    let mut transaction: sqlx::Transaction<'_, sqlx::Postgres> = db_pool.begin().await?;
+
     snapshot_store::insert_snapshots(&mut transaction, &snap_shot_array).await?;
 
     transaction.commit().await?;
@@ -225,14 +391,35 @@ pub async fn create_snapshots(
 }
 
 async fn handle_snap_shot_for_url(
-    url: &str,
+    target: &CaptureTarget,
     image_type: SnapShotType,
+    options: &CaptureOptions,
+    settings: &CaptureSettings,
+    session_pool: &SessionPool,
+    events: &SnapShotBatchEventSender,
+    cancellation: &CancellationToken,
 ) -> Result<Vec<Result<RawImage, Error>>, Error> {
-    tracing::debug!("Capturing screen shots for url: {}", url);
+    let url = target.url.as_str();
 
-    let image_params = get_screenshot_params_by_url(url, &image_type).await?;
+    tracing::debug!("Capturing screen shots for url: {}", url);
 
-    let results = capture_screenshots::capture_screenshots(&image_params).await?;
+    let mut image_params = get_screenshot_params_by_url(url, &image_type, options).await?;
+
+    // Both sides keep the same names when browsers are compared with each other
+    if let Some(browser) = target.browser {
+        for param in image_params.iter_mut() {
+            param.browser = browser;
+        }
+    }
+
+    let results = capture_screenshots::capture_screenshots(
+        &image_params,
+        settings,
+        session_pool,
+        events,
+        cancellation,
+    )
+    .await?;
 
     let num_ok_results = results.iter().filter(|r| r.is_ok()).count();
 
@@ -246,10 +433,18 @@ async fn handle_snap_shot_for_url(
     Ok(results)
 }
 
+async fn report_progress(db_pool: &sqlx::Pool<sqlx::Postgres>, job_id: &Uuid, progress: f64) {
+    // A failed progress update should not fail the whole batch
+    let _ =
+        snapshot_batch_job_store::update_snap_shot_batch_job_progress(db_pool, job_id, progress)
+            .await;
+}
+
 fn create_folders(folder_name: &str) -> Result<(), anyhow::Error> {
     fs::create_dir_all(folder_name)?;
     fs::create_dir_all(format!("{}/deleted", folder_name))?;
     fs::create_dir_all(format!("{}/created", folder_name))?;
+    fs::create_dir_all(format!("{}/unstable", folder_name))?;
     fs::create_dir_all(format!("{}/diff", folder_name))?;
 
     Ok(())
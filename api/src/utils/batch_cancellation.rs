@@ -0,0 +1,61 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Keeps a cancellation token per running batch so that a request can stop
+/// the captures and comparisons that belong to it.
+#[derive(Clone, Default)]
+pub struct SnapShotBatchCancellations {
+    tokens: Arc<Mutex<HashMap<Uuid, CancellationToken>>>,
+}
+
+impl SnapShotBatchCancellations {
+    pub fn open(&self, batch_id: Uuid) -> CancellationToken {
+        let token = CancellationToken::new();
+
+        self.tokens.lock().unwrap().insert(batch_id, token.clone());
+
+        token
+    }
+
+    /// Returns `false` when the batch is not running
+    pub fn cancel(&self, batch_id: &Uuid) -> bool {
+        match self.tokens.lock().unwrap().get(batch_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn close(&self, batch_id: &Uuid) {
+        self.tokens.lock().unwrap().remove(batch_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_cancellations() {
+        let cancellations = SnapShotBatchCancellations::default();
+        let batch_id = Uuid::new_v4();
+
+        assert!(!cancellations.cancel(&batch_id));
+
+        let token = cancellations.open(batch_id);
+
+        assert!(cancellations.cancel(&batch_id));
+        assert!(token.is_cancelled());
+
+        cancellations.close(&batch_id);
+
+        assert!(!cancellations.cancel(&batch_id));
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use futures_util::{stream, Stream};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::models::snapshot_batch_event::SnapShotBatchEvent;
+
+const CHANNEL_CAPACITY: usize = 1024;
+
+pub type SnapShotBatchEventSender = broadcast::Sender<SnapShotBatchEvent>;
+
+/// Keeps one broadcast channel per running batch so that any number of
+/// clients can follow its progress.
+#[derive(Clone, Default)]
+pub struct SnapShotBatchEvents {
+    channels: Arc<Mutex<HashMap<Uuid, SnapShotBatchEventSender>>>,
+}
+
+impl SnapShotBatchEvents {
+    pub fn open(&self, batch_id: Uuid) -> SnapShotBatchEventSender {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+        self.channels
+            .lock()
+            .unwrap()
+            .insert(batch_id, sender.clone());
+
+        sender
+    }
+
+    pub fn subscribe(&self, batch_id: &Uuid) -> Option<broadcast::Receiver<SnapShotBatchEvent>> {
+        self.channels
+            .lock()
+            .unwrap()
+            .get(batch_id)
+            .map(|sender| sender.subscribe())
+    }
+
+    pub fn close(&self, batch_id: &Uuid) {
+        self.channels.lock().unwrap().remove(batch_id);
+    }
+}
+
+/// Sending only fails when nobody is listening, which is not an error for us
+pub fn emit(sender: &SnapShotBatchEventSender, event: SnapShotBatchEvent) {
+    let _ = sender.send(event);
+}
+
+/// Turns a subscription into a stream that ends after the batch summary
+pub fn into_stream(
+    receiver: broadcast::Receiver<SnapShotBatchEvent>,
+) -> impl Stream<Item = SnapShotBatchEvent> {
+    stream::unfold(Some(receiver), |receiver| async move {
+        let mut receiver = receiver?;
+
+        loop {
+            match receiver.recv().await {
+                Ok(event @ SnapShotBatchEvent::Summary { .. }) => return Some((event, None)),
+                Ok(event) => return Some((event, Some(receiver))),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Batch event listener lagged behind by {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        snapshot_batch_event::CompareOutcome, snapshot_batch_job::SnapShotBatchJobStatus,
+    };
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn test_batch_events() {
+        let events = SnapShotBatchEvents::default();
+        let batch_id = Uuid::new_v4();
+
+        assert!(events.subscribe(&batch_id).is_none());
+
+        let sender = events.open(batch_id);
+        let mut receiver = events.subscribe(&batch_id).unwrap();
+
+        emit(
+            &sender,
+            SnapShotBatchEvent::compared("story".to_string(), CompareOutcome::Unchanged),
+        );
+
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            SnapShotBatchEvent::compared("story".to_string(), CompareOutcome::Unchanged)
+        );
+
+        events.close(&batch_id);
+
+        assert!(events.subscribe(&batch_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_into_stream_ends_after_summary() {
+        let events = SnapShotBatchEvents::default();
+        let batch_id = Uuid::new_v4();
+
+        let sender = events.open(batch_id);
+        let stream = into_stream(events.subscribe(&batch_id).unwrap());
+
+        let summary = SnapShotBatchEvent::Summary {
+            status: SnapShotBatchJobStatus::Completed,
+            error: None,
+            diff_found: 1,
+            created: 0,
+            deleted: 0,
+            unstable: 0,
+        };
+
+        emit(
+            &sender,
+            SnapShotBatchEvent::compared("story".to_string(), CompareOutcome::DiffFound),
+        );
+        emit(&sender, summary.clone());
+        emit(
+            &sender,
+            SnapShotBatchEvent::compared("story".to_string(), CompareOutcome::Unchanged),
+        );
+
+        let received: Vec<SnapShotBatchEvent> = stream.collect().await;
+
+        assert_eq!(
+            received,
+            vec![
+                SnapShotBatchEvent::compared("story".to_string(), CompareOutcome::DiffFound),
+                summary
+            ]
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use anyhow::Error;
+use async_trait::async_trait;
+use chromiumoxide::{
+    cdp::{
+        browser_protocol::{
+            emulation::{
+                MediaFeature, SetDeviceMetricsOverrideParams, SetEmulatedMediaParams,
+                SetTouchEmulationEnabledParams, SetUserAgentOverrideParams,
+            },
+            input::{
+                DispatchKeyEventParams, DispatchKeyEventType, DispatchMouseEventParams,
+                DispatchMouseEventType, MouseButton,
+            },
+            page::{CaptureScreenshotFormat, Viewport},
+        },
+        js_protocol::runtime::{CallArgument, CallFunctionOnParams},
+    },
+    layout::Point,
+    page::ScreenshotParams,
+    Browser, BrowserConfig, Page,
+};
+use futures_util::StreamExt;
+use serde_json::Value;
+use tokio::{sync::Mutex, task::JoinHandle};
+
+use crate::models::{
+    browser::Browser as CaptureBrowser, device::Device, interaction::InteractionState,
+    media::MediaEmulation,
+};
+
+use super::{focus, CaptureBackend, CapturePage, PageScreenshot};
+
+/// Viewport chromiumoxide gives new pages
+const DEFAULT_VIEWPORT: (u32, u32) = (800, 600);
+
+const PAGE_SIZE_SCRIPT: &str = r"
+    return [document.documentElement.scrollWidth, document.documentElement.scrollHeight];
+";
+
+/// Launches a headless Chromium on first use and opens a tab of it for every page,
+/// so stories can be captured without a Selenium node
+pub struct CdpBackend {
+    chrome_path: Option<String>,
+    browser: Mutex<Option<RunningBrowser>>,
+}
+
+struct RunningBrowser {
+    browser: Browser,
+    handler: JoinHandle<()>,
+}
+
+impl CdpBackend {
+    /// Without a path to the executable Chromium is looked for where it is usually installed
+    pub fn new(chrome_path: Option<String>) -> Self {
+        Self {
+            chrome_path,
+            browser: Mutex::new(None),
+        }
+    }
+
+    async fn launch(&self) -> Result<RunningBrowser, Error> {
+        let mut config = BrowserConfig::builder()
+            .no_sandbox()
+            .window_size(DEFAULT_VIEWPORT.0, DEFAULT_VIEWPORT.1)
+            .args([
+                "--disable-gpu",
+                "--disable-dev-shm-usage",
+                "--hide-scrollbars",
+            ]);
+
+        if let Some(chrome_path) = &self.chrome_path {
+            config = config.chrome_executable(chrome_path);
+        }
+
+        let config = config.build().map_err(Error::msg)?;
+
+        tracing::debug!("Launching chromium");
+        let (browser, mut handler) = Browser::launch(config).await.map_err(|err| {
+            tracing::error!("Unable to launch chromium {}", err);
+            err
+        })?;
+
+        // The handler drives the connection to the browser and has to be polled for as long as it runs
+        let handler = tokio::spawn(async move {
+            while let Some(event) = handler.next().await {
+                if let Err(err) = event {
+                    tracing::debug!("Chromium connection closed {}", err);
+                    break;
+                }
+            }
+        });
+
+        Ok(RunningBrowser { browser, handler })
+    }
+}
+
+#[async_trait]
+impl CaptureBackend for CdpBackend {
+    async fn open_page(
+        &self,
+        browser: CaptureBrowser,
+        device: Option<&Device>,
+    ) -> Result<Box<dyn CapturePage>, Error> {
+        if browser != CaptureBrowser::Chrome {
+            return Err(Error::msg(format!(
+                "The cdp capture backend only captures in chrome, not in {}",
+                browser
+            )));
+        }
+
+        let mut running = self.browser.lock().await;
+
+        if running.is_none() {
+            *running = Some(self.launch().await?);
+        }
+
+        let page = match running
+            .as_ref()
+            .unwrap()
+            .browser
+            .new_page("about:blank")
+            .await
+        {
+            Ok(page) => page,
+            Err(err) => {
+                // Launch a new browser next time in case this one crashed
+                if let Some(browser) = running.take() {
+                    browser.handler.abort();
+                }
+                return Err(err.into());
+            }
+        };
+
+        let page = CdpPage {
+            page,
+            device: device.cloned(),
+        };
+
+        if let Some(device) = device {
+            page.emulate_device(device).await?;
+        }
+
+        Ok(Box::new(page))
+    }
+
+    async fn close(&self) {
+        if let Some(mut running) = self.browser.lock().await.take() {
+            if let Err(err) = running.browser.close().await {
+                tracing::error!("Unable to close chromium {}", err);
+            }
+            let _ = running.browser.wait().await;
+            running.handler.abort();
+        }
+    }
+}
+
+pub struct CdpPage {
+    page: Page,
+    /// Gives every viewport its pixel ratio
+    device: Option<Device>,
+}
+
+impl CdpPage {
+    async fn emulate_device(&self, device: &Device) -> Result<(), Error> {
+        if let Some(user_agent) = &device.user_agent {
+            self.page
+                .execute(SetUserAgentOverrideParams::new(user_agent.clone()))
+                .await?;
+        }
+
+        if device.touch {
+            self.page
+                .execute(SetTouchEmulationEnabledParams::new(true))
+                .await?;
+        }
+
+        self.set_viewport(device.width, device.height).await
+    }
+
+    async fn call_function(
+        &self,
+        function_declaration: String,
+        args: Vec<Value>,
+    ) -> Result<Value, Error> {
+        let arguments: Vec<CallArgument> = args
+            .into_iter()
+            .map(|arg| CallArgument::builder().value(arg).build())
+            .collect();
+
+        let params = CallFunctionOnParams::builder()
+            .function_declaration(function_declaration)
+            .arguments(arguments)
+            .await_promise(true)
+            .return_by_value(true)
+            .build()
+            .map_err(Error::msg)?;
+
+        let result = self.page.evaluate_function(params).await?;
+
+        Ok(result.value().cloned().unwrap_or(Value::Null))
+    }
+
+    async fn dispatch_mouse_event(
+        &self,
+        event: DispatchMouseEventType,
+        point: Point,
+    ) -> Result<(), Error> {
+        self.page
+            .execute(
+                DispatchMouseEventParams::builder()
+                    .r#type(event)
+                    .x(point.x)
+                    .y(point.y)
+                    .button(MouseButton::Left)
+                    .click_count(1)
+                    .build()
+                    .map_err(Error::msg)?,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Captures the whole page beyond the viewport at the emulated device metrics.
+/// chromiumoxide's full page screenshots override the metrics with a pixel ratio of 1 and
+/// clear them afterwards, which would undo the device emulation
+fn full_page_screenshot_params(width: f64, height: f64) -> ScreenshotParams {
+    ScreenshotParams::builder()
+        .format(CaptureScreenshotFormat::Png)
+        .capture_beyond_viewport(true)
+        .clip(Viewport {
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+            scale: 1.0,
+        })
+        .build()
+}
+
+#[async_trait]
+impl CapturePage for CdpPage {
+    async fn set_viewport(&self, width: u32, height: u32) -> Result<(), Error> {
+        let (device_pixel_ratio, mobile) = match &self.device {
+            Some(device) => (device.device_pixel_ratio, device.mobile),
+            None => (1.0, false),
+        };
+
+        self.page
+            .execute(SetDeviceMetricsOverrideParams::new(
+                width,
+                height,
+                device_pixel_ratio,
+                mobile,
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn reset_viewport(&self) -> Result<(), Error> {
+        self.set_viewport(DEFAULT_VIEWPORT.0, DEFAULT_VIEWPORT.1)
+            .await
+    }
+
+    async fn emulate_media(&self, media: Option<MediaEmulation>) -> Result<(), Error> {
+        // Every call replaces the previous emulation, so empty values reset it
+        let params = SetEmulatedMediaParams::builder()
+            .media(media.and_then(|media| media.media_type()).unwrap_or(""))
+            .features(
+                media
+                    .and_then(|media| media.media_feature())
+                    .map(|(name, value)| MediaFeature::new(name, value)),
+            )
+            .build();
+
+        self.page.execute(params).await?;
+
+        Ok(())
+    }
+
+    async fn goto(&self, url: &str) -> Result<(), Error> {
+        self.page.goto(url).await?;
+
+        Ok(())
+    }
+
+    async fn execute(&self, script: &str, args: Vec<Value>) -> Result<Value, Error> {
+        self.call_function(format!("function () {{ {} }}", script), args)
+            .await
+    }
+
+    async fn execute_async(&self, script: &str, args: Vec<Value>) -> Result<Value, Error> {
+        // Hands the script a callback as its last argument, like WebDriver does
+        let function_declaration = format!(
+            r"function () {{
+                var args = Array.prototype.slice.call(arguments);
+                return new Promise(function (resolve) {{
+                    args.push(resolve);
+                    (function () {{ {} }}).apply(null, args);
+                }});
+            }}",
+            script
+        );
+
+        self.call_function(function_declaration, args).await
+    }
+
+    async fn interact(&self, selector: &str, state: InteractionState) -> Result<(), Error> {
+        match state {
+            InteractionState::Hover | InteractionState::Active => {
+                let element = self.page.find_element(selector).await?;
+                element.hover().await?;
+
+                if state == InteractionState::Active {
+                    let point = element.clickable_point().await?;
+                    self.dispatch_mouse_event(DispatchMouseEventType::MousePressed, point)
+                        .await?;
+                }
+            }
+            InteractionState::Focus => focus(self, selector, false).await?,
+            InteractionState::FocusVisible => {
+                // Chromium only draws the focus ring when the last input came from the keyboard
+                for key_event in [DispatchKeyEventType::KeyDown, DispatchKeyEventType::KeyUp] {
+                    self.page
+                        .execute(
+                            DispatchKeyEventParams::builder()
+                                .r#type(key_event)
+                                .key("Shift")
+                                .build()
+                                .map_err(Error::msg)?,
+                        )
+                        .await?;
+                }
+
+                focus(self, selector, true).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn release_interaction(&self) -> Result<(), Error> {
+        let corner = Point::new(0.0, 0.0);
+
+        self.page.move_mouse(corner).await?;
+        self.dispatch_mouse_event(DispatchMouseEventType::MouseReleased, corner)
+            .await
+    }
+
+    async fn screenshot(&self) -> Result<PageScreenshot, Error> {
+        let size = self.execute(PAGE_SIZE_SCRIPT, vec![]).await?;
+        let [width, height]: [f64; 2] = serde_json::from_value(size)?;
+
+        let image = self
+            .page
+            .screenshot(full_page_screenshot_params(width, height))
+            .await?;
+
+        Ok(PageScreenshot {
+            image,
+            width,
+            height,
+        })
+    }
+
+    async fn viewport_screenshot(&self) -> Result<Vec<u8>, Error> {
+        Ok(self
+            .page
+            .screenshot(
+                ScreenshotParams::builder()
+                    .format(CaptureScreenshotFormat::Png)
+                    .build(),
+            )
+            .await?)
+    }
+
+    async fn is_healthy(&self) -> bool {
+        self.page.evaluate("1").await.is_ok()
+    }
+
+    async fn close(self: Box<Self>) {
+        if let Err(err) = self.page.close().await {
+            tracing::error!("Unable to close chromium page {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_page_screenshot_params_keep_device_metrics() {
+        let params = full_page_screenshot_params(1440.0, 3000.0);
+
+        assert_ne!(params.full_page, Some(true));
+        assert_eq!(params.cdp_params.capture_beyond_viewport, Some(true));
+
+        let clip = params.cdp_params.clip.unwrap();
+        assert_eq!((clip.x, clip.y), (0.0, 0.0));
+        assert_eq!((clip.width, clip.height), (1440.0, 3000.0));
+        assert_eq!(clip.scale, 1.0);
+    }
+
+    /// Needs a Chromium, so it only runs when CHROME_PATH points at one
+    #[tokio::test]
+    async fn test_screenshot_keeps_device_pixel_ratio() {
+        let Ok(chrome_path) = std::env::var("CHROME_PATH") else {
+            return;
+        };
+
+        let backend = CdpBackend::new(Some(chrome_path));
+        let device = Device::preset("desktop-2x").unwrap();
+        let page = backend
+            .open_page(CaptureBrowser::Chrome, Some(&device))
+            .await
+            .unwrap();
+
+        page.goto("data:text/html,<div style='height:2000px'></div>")
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let screenshot = page.screenshot().await.unwrap();
+            let image = image::load_from_memory(&screenshot.image).unwrap();
+
+            assert_eq!(screenshot.width, 1440.0);
+            assert_eq!(image.width() as f64, screenshot.width * 2.0);
+            assert_eq!(image.height() as f64, screenshot.height * 2.0);
+        }
+
+        let ratio = page
+            .execute("return window.devicePixelRatio;", vec![])
+            .await
+            .unwrap();
+        assert_eq!(ratio, 2.0);
+
+        page.close().await;
+        backend.close().await;
+    }
+}
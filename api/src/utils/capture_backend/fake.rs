@@ -0,0 +1,320 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io::Cursor,
+    sync::Mutex,
+};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use image::{ImageFormat, Rgba, RgbaImage};
+use serde_json::Value;
+
+use crate::models::{
+    browser::Browser, device::Device, interaction::InteractionState, media::MediaEmulation,
+};
+
+use super::{CaptureBackend, CapturePage, PageScreenshot};
+
+const DEFAULT_VIEWPORT: (u32, u32) = (800, 600);
+
+/// Renders every url as a single color derived from it, so the same story always
+/// looks the same and different stories look different. There is no DOM, scripts
+/// are answered with what was configured for them and `true` otherwise. Emulated media
+/// and interactions change the color as if the url had a `#media` or `#state` fragment.
+#[derive(Clone)]
+pub struct FakeBackend {
+    colors: Vec<(String, Rgba<u8>)>,
+    script_results: Vec<(String, Value)>,
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        Self {
+            colors: vec![],
+            script_results: vec![
+                // Nothing is found to mask or crop to
+                (
+                    "querySelectorAll(arguments[0])".to_string(),
+                    serde_json::json!([]),
+                ),
+                // Resources are loaded already, so the network is idle
+                (
+                    "getEntriesByType('resource')".to_string(),
+                    serde_json::json!(0),
+                ),
+            ],
+        }
+    }
+}
+
+impl FakeBackend {
+    /// Renders urls containing `url_part` in `color`, e.g. to make a story change between versions
+    pub fn with_color(mut self, url_part: &str, color: [u8; 4]) -> Self {
+        self.colors.insert(0, (url_part.to_string(), Rgba(color)));
+        self
+    }
+
+    /// Answers scripts containing `script_part` with `result`
+    pub fn with_script_result(mut self, script_part: &str, result: Value) -> Self {
+        self.script_results
+            .insert(0, (script_part.to_string(), result));
+        self
+    }
+
+    fn color_of(&self, url: &str) -> Rgba<u8> {
+        if let Some((_, color)) = self.colors.iter().find(|(part, _)| url.contains(part)) {
+            return *color;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        url.hash(&mut hasher);
+        let [r, g, b, ..] = hasher.finish().to_le_bytes();
+
+        Rgba([r, g, b, 255])
+    }
+}
+
+#[async_trait]
+impl CaptureBackend for FakeBackend {
+    async fn open_page(
+        &self,
+        _browser: Browser,
+        device: Option<&Device>,
+    ) -> Result<Box<dyn CapturePage>, Error> {
+        Ok(Box::new(FakePage {
+            backend: self.clone(),
+            device_pixel_ratio: device.map_or(1.0, |device| device.device_pixel_ratio),
+            state: Mutex::new(FakePageState {
+                url: "about:blank".to_string(),
+                viewport: device.map_or(DEFAULT_VIEWPORT, |device| (device.width, device.height)),
+                media: None,
+                interaction: None,
+            }),
+        }))
+    }
+}
+
+pub struct FakePage {
+    backend: FakeBackend,
+    device_pixel_ratio: f64,
+    state: Mutex<FakePageState>,
+}
+
+struct FakePageState {
+    url: String,
+    viewport: (u32, u32),
+    media: Option<MediaEmulation>,
+    interaction: Option<InteractionState>,
+}
+
+impl FakePage {
+    fn script_result(&self, script: &str) -> Value {
+        self.backend
+            .script_results
+            .iter()
+            .find(|(part, _)| script.contains(part.as_str()))
+            .map(|(_, result)| result.clone())
+            .unwrap_or(Value::Bool(true))
+    }
+
+    /// Png of the page, which is exactly as large as the viewport. Returns its size in
+    /// css pixels, which the image exceeds by the device pixel ratio
+    fn render(&self) -> Result<(Vec<u8>, u32, u32), Error> {
+        let (url, (width, height)) = {
+            let state = self.state.lock().unwrap();
+            let mut url = state.url.clone();
+            if let Some(media) = state.media {
+                url = format!("{}#{}", url, media);
+            }
+            if let Some(interaction) = state.interaction {
+                url = format!("{}#{}", url, interaction);
+            }
+            (url, state.viewport)
+        };
+
+        let page = RgbaImage::from_pixel(
+            (width as f64 * self.device_pixel_ratio).round() as u32,
+            (height as f64 * self.device_pixel_ratio).round() as u32,
+            self.backend.color_of(&url),
+        );
+
+        let mut image = vec![];
+        page.write_to(&mut Cursor::new(&mut image), ImageFormat::Png)?;
+
+        Ok((image, width, height))
+    }
+}
+
+#[async_trait]
+impl CapturePage for FakePage {
+    async fn set_viewport(&self, width: u32, height: u32) -> Result<(), Error> {
+        self.state.lock().unwrap().viewport = (width, height);
+        Ok(())
+    }
+
+    async fn reset_viewport(&self) -> Result<(), Error> {
+        self.set_viewport(DEFAULT_VIEWPORT.0, DEFAULT_VIEWPORT.1)
+            .await
+    }
+
+    async fn emulate_media(&self, media: Option<MediaEmulation>) -> Result<(), Error> {
+        self.state.lock().unwrap().media = media;
+        Ok(())
+    }
+
+    async fn goto(&self, url: &str) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.url = url.to_string();
+        state.interaction = None;
+        Ok(())
+    }
+
+    async fn execute(&self, script: &str, _args: Vec<Value>) -> Result<Value, Error> {
+        Ok(self.script_result(script))
+    }
+
+    async fn execute_async(&self, script: &str, _args: Vec<Value>) -> Result<Value, Error> {
+        Ok(self.script_result(script))
+    }
+
+    async fn interact(&self, _selector: &str, state: InteractionState) -> Result<(), Error> {
+        self.state.lock().unwrap().interaction = Some(state);
+        Ok(())
+    }
+
+    async fn release_interaction(&self) -> Result<(), Error> {
+        self.state.lock().unwrap().interaction = None;
+        Ok(())
+    }
+
+    async fn screenshot(&self) -> Result<PageScreenshot, Error> {
+        let (image, width, height) = self.render()?;
+
+        Ok(PageScreenshot {
+            image,
+            width: width as f64,
+            height: height as f64,
+        })
+    }
+
+    async fn viewport_screenshot(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.render()?.0)
+    }
+
+    async fn is_healthy(&self) -> bool {
+        true
+    }
+
+    async fn close(self: Box<Self>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn screenshot_of(backend: &FakeBackend, url: &str) -> PageScreenshot {
+        let page = backend.open_page(Browser::Chrome, None).await.unwrap();
+        page.goto(url).await.unwrap();
+        page.screenshot().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fake_backend_is_deterministic() {
+        let backend = FakeBackend::default();
+
+        let first = screenshot_of(&backend, "http://storybook/iframe.html?id=button").await;
+        let second = screenshot_of(&backend, "http://storybook/iframe.html?id=button").await;
+        let other = screenshot_of(&backend, "http://storybook/iframe.html?id=input").await;
+
+        assert_eq!(first.image, second.image);
+        assert_ne!(first.image, other.image);
+        assert_eq!((first.width, first.height), (800.0, 600.0));
+    }
+
+    #[tokio::test]
+    async fn test_fake_backend_uses_configured_color_and_viewport() {
+        let backend = FakeBackend::default().with_color("id=button", [255, 0, 0, 255]);
+        let page = backend.open_page(Browser::Chrome, None).await.unwrap();
+
+        page.set_viewport(20, 10).await.unwrap();
+        page.goto("http://storybook/iframe.html?id=button")
+            .await
+            .unwrap();
+        let screenshot = page.screenshot().await.unwrap();
+
+        let image = image::load_from_memory(&screenshot.image)
+            .unwrap()
+            .to_rgba8();
+
+        assert_eq!(image.dimensions(), (20, 10));
+        assert_eq!(*image.get_pixel(5, 5), Rgba([255, 0, 0, 255]));
+    }
+
+    #[tokio::test]
+    async fn test_fake_backend_renders_at_device_pixel_ratio() {
+        let device = Device::preset("desktop-2x").unwrap();
+        let page = FakeBackend::default()
+            .open_page(Browser::Chrome, Some(&device))
+            .await
+            .unwrap();
+
+        let screenshot = page.screenshot().await.unwrap();
+        let image = image::load_from_memory(&screenshot.image).unwrap();
+
+        assert_eq!((screenshot.width, screenshot.height), (1440.0, 900.0));
+        assert_eq!((image.width(), image.height()), (2880, 1800));
+    }
+
+    #[tokio::test]
+    async fn test_fake_backend_renders_interactions() {
+        let backend = FakeBackend::default().with_color("#hover", [0, 0, 255, 255]);
+        let page = backend.open_page(Browser::Chrome, None).await.unwrap();
+
+        page.goto("http://storybook/iframe.html?id=button")
+            .await
+            .unwrap();
+        let idle = page.screenshot().await.unwrap();
+
+        page.interact("button", InteractionState::Hover)
+            .await
+            .unwrap();
+        let hovered = page.screenshot().await.unwrap();
+
+        page.release_interaction().await.unwrap();
+        let released = page.screenshot().await.unwrap();
+
+        let image = image::load_from_memory(&hovered.image).unwrap().to_rgba8();
+
+        assert_eq!(*image.get_pixel(5, 5), Rgba([0, 0, 255, 255]));
+        assert_eq!(idle.image, released.image);
+    }
+
+    #[tokio::test]
+    async fn test_fake_backend_renders_emulated_media() {
+        let backend = FakeBackend::default().with_color("#dark", [0, 0, 0, 255]);
+        let page = backend.open_page(Browser::Chrome, None).await.unwrap();
+
+        page.goto("http://storybook/iframe.html?id=button")
+            .await
+            .unwrap();
+        let light = page.screenshot().await.unwrap();
+
+        // Emulation outlasts navigations, as it does in browsers
+        page.emulate_media(Some(MediaEmulation::Dark))
+            .await
+            .unwrap();
+        page.goto("http://storybook/iframe.html?id=button")
+            .await
+            .unwrap();
+        let dark = page.screenshot().await.unwrap();
+
+        page.emulate_media(None).await.unwrap();
+        let reset = page.screenshot().await.unwrap();
+
+        let image = image::load_from_memory(&dark.image).unwrap().to_rgba8();
+
+        assert_eq!(*image.get_pixel(5, 5), Rgba([0, 0, 0, 255]));
+        assert_eq!(light.image, reset.image);
+    }
+}
@@ -0,0 +1,182 @@
+use std::{fmt, str::FromStr, sync::Arc};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::models::{
+    browser::Browser, device::Device, interaction::InteractionState, media::MediaEmulation,
+};
+
+use super::env_variables::EnvVariables;
+
+pub mod cdp;
+pub mod fake;
+pub mod webdriver;
+
+/// Focuses the first element matching a selector. `focusVisible` is only honoured by
+/// some browsers, the others show a focus ring when the last input was the keyboard
+const FOCUS_SCRIPT: &str = r"
+    var element = document.querySelector(arguments[0]);
+    if (!element) {
+        return false;
+    }
+    element.focus({ focusVisible: arguments[1] });
+    return true;
+";
+
+/// Browser that stories are captured with
+#[async_trait]
+pub trait CaptureBackend: Send + Sync {
+    /// Opens a page of its own in the browser, on which one story after another is captured.
+    /// The page emulates the device for as long as it is open
+    async fn open_page(
+        &self,
+        browser: Browser,
+        device: Option<&Device>,
+    ) -> Result<Box<dyn CapturePage>, Error>;
+
+    /// Shuts down what the backend keeps running besides its pages
+    async fn close(&self) {}
+}
+
+/// A single page of a backend, used by one story at a time
+#[async_trait]
+pub trait CapturePage: Send + Sync {
+    /// Sizes the page itself, not the window with its browser chrome
+    async fn set_viewport(&self, width: u32, height: u32) -> Result<(), Error>;
+
+    /// Gives the page the size of a freshly opened one
+    async fn reset_viewport(&self) -> Result<(), Error>;
+
+    /// Renders the page in the css media from now on, or as a screen again when `None`
+    async fn emulate_media(&self, media: Option<MediaEmulation>) -> Result<(), Error>;
+
+    /// Loads the url and waits for the page to load
+    async fn goto(&self, url: &str) -> Result<(), Error>;
+
+    /// Runs the body of a function called with `args`, returning what it returns
+    async fn execute(&self, script: &str, args: Vec<Value>) -> Result<Value, Error>;
+
+    /// Runs the body of a function called with `args` and a callback, returning
+    /// what the callback is called with
+    async fn execute_async(&self, script: &str, args: Vec<Value>) -> Result<Value, Error>;
+
+    /// Puts the first element matching the css selector in the state
+    async fn interact(&self, selector: &str, state: InteractionState) -> Result<(), Error>;
+
+    /// Lets go of what the last interaction pressed and moves the pointer out of the way,
+    /// so the next story starts out idle
+    async fn release_interaction(&self) -> Result<(), Error>;
+
+    /// Png of the whole page
+    async fn screenshot(&self) -> Result<PageScreenshot, Error>;
+
+    /// Png of the part of the page that is scrolled into view
+    async fn viewport_screenshot(&self) -> Result<Vec<u8>, Error>;
+
+    /// Whether the page still responds and can be used again
+    async fn is_healthy(&self) -> bool;
+
+    async fn close(self: Box<Self>);
+}
+
+/// Focuses an element with a script, which behaves the same in every browser
+async fn focus(page: &dyn CapturePage, selector: &str, visible: bool) -> Result<(), Error> {
+    let focused = page
+        .execute(
+            FOCUS_SCRIPT,
+            vec![Value::String(selector.to_string()), Value::Bool(visible)],
+        )
+        .await?;
+
+    if focused != Value::Bool(true) {
+        return Err(Error::msg(format!(
+            "No element matches {} to focus",
+            selector
+        )));
+    }
+
+    Ok(())
+}
+
+pub struct PageScreenshot {
+    pub image: Vec<u8>,
+    /// Width of the page in css pixels, which differs from the image on high density displays
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CaptureBackendKind {
+    /// A Selenium node or any other WebDriver server
+    #[default]
+    WebDriver,
+    /// A headless Chromium launched locally and driven over the Chrome DevTools Protocol
+    Cdp,
+    /// Renders deterministic images without a browser
+    Fake,
+}
+
+impl fmt::Display for CaptureBackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaptureBackendKind::WebDriver => write!(f, "webdriver"),
+            CaptureBackendKind::Cdp => write!(f, "cdp"),
+            CaptureBackendKind::Fake => write!(f, "fake"),
+        }
+    }
+}
+
+impl FromStr for CaptureBackendKind {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "webdriver" | "selenium" => Ok(CaptureBackendKind::WebDriver),
+            "cdp" | "chromium" => Ok(CaptureBackendKind::Cdp),
+            "fake" => Ok(CaptureBackendKind::Fake),
+            _ => Err(Error::msg(format!(
+                "Unknown capture backend {}, expected webdriver, cdp or fake",
+                value
+            ))),
+        }
+    }
+}
+
+pub fn from_env(env_variables: &EnvVariables) -> Arc<dyn CaptureBackend> {
+    match env_variables.capture_backend {
+        // Both are required for the webdriver backend when the variables are read
+        CaptureBackendKind::WebDriver => Arc::new(webdriver::WebDriverBackend::new(format!(
+            "http://{}:{}",
+            env_variables.selenium_host.as_deref().unwrap_or_default(),
+            env_variables.selenium_port.as_deref().unwrap_or_default()
+        ))),
+        CaptureBackendKind::Cdp => {
+            Arc::new(cdp::CdpBackend::new(env_variables.chrome_path.clone()))
+        }
+        CaptureBackendKind::Fake => Arc::new(fake::FakeBackend::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_capture_backend_kind() {
+        assert_eq!(
+            "webdriver".parse::<CaptureBackendKind>().unwrap(),
+            CaptureBackendKind::WebDriver
+        );
+        assert_eq!(
+            " CDP ".parse::<CaptureBackendKind>().unwrap(),
+            CaptureBackendKind::Cdp
+        );
+        assert_eq!(
+            "fake".parse::<CaptureBackendKind>().unwrap(),
+            CaptureBackendKind::Fake
+        );
+        assert!("playwright".parse::<CaptureBackendKind>().is_err());
+    }
+}
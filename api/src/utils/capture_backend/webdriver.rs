@@ -0,0 +1,405 @@
+use anyhow::Error;
+use async_trait::async_trait;
+use fantoccini::{
+    actions::{InputSource, KeyAction, KeyActions, MouseActions, PointerAction, MOUSE_BUTTON_LEFT},
+    key::Key,
+    wd::WebDriverCompatibleCommand,
+    Client, ClientBuilder, Locator,
+};
+use serde_json::Value;
+
+use crate::models::{
+    browser::Browser, device::Device, interaction::InteractionState, media::MediaEmulation,
+};
+
+use super::{focus, CaptureBackend, CapturePage, PageScreenshot};
+
+/// Window size of a fresh headless Chrome session
+const DEFAULT_WINDOW_SIZE: (u32, u32) = (800, 600);
+
+const CHROMIUM_ARGS: [&str; 4] = [
+    "--headless",
+    "--disable-gpu",
+    "--no-sandbox",
+    "--disable-dev-shm-usage",
+];
+
+/// Opens a WebDriver session, usually on a Selenium node, for every page
+pub struct WebDriverBackend {
+    webdriver_url: String,
+}
+
+impl WebDriverBackend {
+    pub fn new(webdriver_url: String) -> Self {
+        Self { webdriver_url }
+    }
+}
+
+#[async_trait]
+impl CaptureBackend for WebDriverBackend {
+    async fn open_page(
+        &self,
+        browser: Browser,
+        device: Option<&Device>,
+    ) -> Result<Box<dyn CapturePage>, Error> {
+        tracing::debug!("Connecting to selenium for {}", browser);
+
+        let client = connect(&self.webdriver_url, browser, device)
+            .await
+            .map_err(|err| {
+                tracing::error!("Unable to connect to selenium {}", err.to_string());
+                err
+            })?;
+
+        Ok(Box::new(WebDriverPage { client, browser }))
+    }
+}
+
+pub struct WebDriverPage {
+    client: Client,
+    browser: Browser,
+}
+
+/// A Chrome DevTools Protocol command, sent through the endpoint chromedriver and
+/// msedgedriver offer for what WebDriver itself cannot do
+#[derive(Debug)]
+struct CdpCommand {
+    /// Prefix of the endpoint, `goog` for chromedriver and `ms` for msedgedriver
+    vendor: &'static str,
+    cmd: &'static str,
+    params: Value,
+}
+
+impl WebDriverCompatibleCommand for CdpCommand {
+    fn endpoint(
+        &self,
+        base_url: &url::Url,
+        session_id: Option<&str>,
+    ) -> Result<url::Url, url::ParseError> {
+        base_url.join(&format!(
+            "session/{}/{}/cdp/execute",
+            session_id.unwrap_or_default(),
+            self.vendor
+        ))
+    }
+
+    fn method_and_body(&self, _request_url: &url::Url) -> (http::Method, Option<String>) {
+        let body = serde_json::json!({ "cmd": self.cmd, "params": self.params });
+
+        (http::Method::POST, Some(body.to_string()))
+    }
+}
+
+/// Parameters of `Emulation.setEmulatedMedia`, where empty values reset the emulation
+fn emulated_media_params(media: Option<MediaEmulation>) -> Value {
+    let features: Vec<Value> = media
+        .and_then(|media| media.media_feature())
+        .map(|(name, value)| serde_json::json!({ "name": name, "value": value }))
+        .into_iter()
+        .collect();
+
+    serde_json::json!({
+        "media": media.and_then(|media| media.media_type()).unwrap_or(""),
+        "features": features,
+    })
+}
+
+#[async_trait]
+impl CapturePage for WebDriverPage {
+    async fn set_viewport(&self, width: u32, height: u32) -> Result<(), Error> {
+        self.client.set_window_size(width, height).await?;
+
+        let inner_size = self
+            .client
+            .execute("return [window.innerWidth, window.innerHeight];", vec![])
+            .await?;
+
+        let (inner_width, inner_height) = match inner_size.as_array().map(|size| size.as_slice()) {
+            Some([inner_width, inner_height]) => (
+                inner_width.as_u64().unwrap_or(width as u64) as u32,
+                inner_height.as_u64().unwrap_or(height as u64) as u32,
+            ),
+            _ => return Ok(()),
+        };
+
+        if inner_width == width && inner_height == height {
+            return Ok(());
+        }
+
+        self.client
+            .set_window_size(
+                width + width.saturating_sub(inner_width),
+                height + height.saturating_sub(inner_height),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn reset_viewport(&self) -> Result<(), Error> {
+        self.client
+            .set_window_size(DEFAULT_WINDOW_SIZE.0, DEFAULT_WINDOW_SIZE.1)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn emulate_media(&self, media: Option<MediaEmulation>) -> Result<(), Error> {
+        let vendor = match (self.browser, media) {
+            (Browser::Chrome, _) => "goog",
+            (Browser::Edge, _) => "ms",
+            // Nothing is emulated that would need resetting
+            (Browser::Firefox, None) => return Ok(()),
+            (Browser::Firefox, Some(media)) => {
+                return Err(Error::msg(format!(
+                    "Firefox can not emulate {} media over WebDriver",
+                    media
+                )))
+            }
+        };
+
+        self.client
+            .issue_cmd(CdpCommand {
+                vendor,
+                cmd: "Emulation.setEmulatedMedia",
+                params: emulated_media_params(media),
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn goto(&self, url: &str) -> Result<(), Error> {
+        self.client.goto(url).await?;
+
+        Ok(())
+    }
+
+    async fn execute(&self, script: &str, args: Vec<Value>) -> Result<Value, Error> {
+        Ok(self.client.execute(script, args).await?)
+    }
+
+    async fn execute_async(&self, script: &str, args: Vec<Value>) -> Result<Value, Error> {
+        Ok(self.client.execute_async(script, args).await?)
+    }
+
+    async fn interact(&self, selector: &str, state: InteractionState) -> Result<(), Error> {
+        match state {
+            InteractionState::Hover | InteractionState::Active => {
+                let element = self.client.find(Locator::Css(selector)).await?;
+
+                let mut pointer =
+                    MouseActions::new("pointer".to_string()).then(PointerAction::MoveToElement {
+                        element,
+                        duration: None,
+                        x: 0,
+                        y: 0,
+                    });
+
+                if state == InteractionState::Active {
+                    pointer = pointer.then(PointerAction::Down {
+                        button: MOUSE_BUTTON_LEFT,
+                    });
+                }
+
+                self.client.perform_actions(pointer).await?;
+            }
+            InteractionState::Focus => focus(self, selector, false).await?,
+            InteractionState::FocusVisible => {
+                // Browsers only draw the focus ring when the last input came from the keyboard
+                let shift = KeyActions::new("keyboard".to_string())
+                    .then(KeyAction::Down {
+                        value: Key::Shift.into(),
+                    })
+                    .then(KeyAction::Up {
+                        value: Key::Shift.into(),
+                    });
+
+                self.client.perform_actions(shift).await?;
+                focus(self, selector, true).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn release_interaction(&self) -> Result<(), Error> {
+        self.client.release_actions().await?;
+
+        let pointer = MouseActions::new("pointer".to_string()).then(PointerAction::MoveTo {
+            duration: None,
+            x: 0,
+            y: 0,
+        });
+        self.client.perform_actions(pointer).await?;
+
+        Ok(())
+    }
+
+    async fn screenshot(&self) -> Result<PageScreenshot, Error> {
+        let element = self.client.find(Locator::XPath("/html")).await?;
+
+        let (_, _, width, height) = element.rectangle().await?;
+        let image = element.screenshot().await?;
+
+        Ok(PageScreenshot {
+            image,
+            width,
+            height,
+        })
+    }
+
+    async fn viewport_screenshot(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.client.screenshot().await?)
+    }
+
+    async fn is_healthy(&self) -> bool {
+        self.client.get_window_size().await.is_ok()
+    }
+
+    async fn close(self: Box<Self>) {
+        // End the session rather than leaving it open on the selenium node
+        if let Err(err) = self.client.close().await {
+            tracing::error!("Unable to close selenium session {}", err);
+        }
+    }
+}
+
+/// Capabilities asking the WebDriver server for a headless session of the browser,
+/// emulating the device if there is one
+fn capabilities(
+    browser: Browser,
+    device: Option<&Device>,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut capabilities = match browser {
+        Browser::Chrome => serde_json::json!({
+            "browserName": "chrome",
+            "goog:chromeOptions": { "args": CHROMIUM_ARGS },
+        }),
+        Browser::Firefox => serde_json::json!({
+            "browserName": "firefox",
+            "moz:firefoxOptions": { "args": ["-headless"] },
+        }),
+        Browser::Edge => serde_json::json!({
+            "browserName": "MicrosoftEdge",
+            "ms:edgeOptions": { "args": CHROMIUM_ARGS },
+        }),
+    };
+
+    if let Some(device) = device {
+        emulate_device(&mut capabilities, browser, device);
+    }
+
+    match capabilities {
+        serde_json::Value::Object(capabilities) => capabilities,
+        _ => unreachable!(),
+    }
+}
+
+/// Chromium based browsers emulate devices through `mobileEmulation`, Firefox only
+/// through preferences that set the pixel ratio, user agent and touch events
+fn emulate_device(capabilities: &mut serde_json::Value, browser: Browser, device: &Device) {
+    match browser {
+        Browser::Chrome | Browser::Edge => {
+            let options = match browser {
+                Browser::Edge => "ms:edgeOptions",
+                _ => "goog:chromeOptions",
+            };
+
+            let mut mobile_emulation = serde_json::json!({
+                "deviceMetrics": {
+                    "width": device.width,
+                    "height": device.height,
+                    "pixelRatio": device.device_pixel_ratio,
+                    "touch": device.touch,
+                    "mobile": device.mobile,
+                },
+            });
+
+            if let Some(user_agent) = &device.user_agent {
+                mobile_emulation["userAgent"] = serde_json::json!(user_agent);
+            }
+
+            capabilities[options]["mobileEmulation"] = mobile_emulation;
+        }
+        Browser::Firefox => {
+            let mut prefs = serde_json::json!({
+                "layout.css.devPixelsPerPx": device.device_pixel_ratio.to_string(),
+                "dom.w3c_touch_events.enabled": if device.touch { 1 } else { 0 },
+            });
+
+            if let Some(user_agent) = &device.user_agent {
+                prefs["general.useragent.override"] = serde_json::json!(user_agent);
+            }
+
+            capabilities["moz:firefoxOptions"]["prefs"] = prefs;
+        }
+    }
+}
+
+async fn connect(
+    webdriver_url: &str,
+    browser: Browser,
+    device: Option<&Device>,
+) -> Result<Client, Error> {
+    let c = ClientBuilder::native()
+        .capabilities(capabilities(browser, device))
+        .connect(webdriver_url)
+        .await?;
+
+    Ok(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_per_browser() {
+        let chrome = capabilities(Browser::Chrome, None);
+        let firefox = capabilities(Browser::Firefox, None);
+        let edge = capabilities(Browser::Edge, None);
+
+        assert_eq!(chrome["browserName"], "chrome");
+        assert!(chrome.contains_key("goog:chromeOptions"));
+        assert_eq!(firefox["browserName"], "firefox");
+        assert_eq!(firefox["moz:firefoxOptions"]["args"][0], "-headless");
+        assert_eq!(edge["browserName"], "MicrosoftEdge");
+        assert!(edge.contains_key("ms:edgeOptions"));
+        assert!(chrome["goog:chromeOptions"]
+            .get("mobileEmulation")
+            .is_none());
+    }
+
+    #[test]
+    fn test_capabilities_emulate_device() {
+        let iphone = Device::preset("iphone-14").unwrap();
+
+        let chrome = capabilities(Browser::Chrome, Some(&iphone));
+        let emulation = &chrome["goog:chromeOptions"]["mobileEmulation"];
+        assert_eq!(emulation["deviceMetrics"]["pixelRatio"], 3.0);
+        assert_eq!(emulation["deviceMetrics"]["touch"], true);
+        assert!(emulation["userAgent"].as_str().unwrap().contains("iPhone"));
+        assert_eq!(chrome["goog:chromeOptions"]["args"][0], "--headless");
+
+        let firefox = capabilities(Browser::Firefox, Some(&iphone));
+        let prefs = &firefox["moz:firefoxOptions"]["prefs"];
+        assert_eq!(prefs["layout.css.devPixelsPerPx"], "3");
+        assert_eq!(prefs["dom.w3c_touch_events.enabled"], 1);
+    }
+
+    #[test]
+    fn test_emulated_media_params() {
+        let dark = emulated_media_params(Some(MediaEmulation::Dark));
+        assert_eq!(dark["media"], "");
+        assert_eq!(dark["features"][0]["name"], "prefers-color-scheme");
+        assert_eq!(dark["features"][0]["value"], "dark");
+
+        let print = emulated_media_params(Some(MediaEmulation::Print));
+        assert_eq!(print["media"], "print");
+        assert_eq!(print["features"], serde_json::json!([]));
+
+        let reset = emulated_media_params(None);
+        assert_eq!(reset, serde_json::json!({ "media": "", "features": [] }));
+    }
+}
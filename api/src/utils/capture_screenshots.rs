@@ -1,89 +1,268 @@
-use std::{env, thread::available_parallelism, time::Duration};
+use std::time::Duration;
 
-use crate::models::{raw_image::RawImage, snapshot::SnapShotType};
+use crate::models::{
+    browser::Browser,
+    capture_options::CaptureOptions,
+    crop::{Crop, CropRect},
+    device::Device,
+    full_page::FullPage,
+    globals::Globals,
+    interaction::Interaction,
+    mask::{Mask, MaskedRegion},
+    media::MediaEmulation,
+    raw_image::RawImage,
+    snapshot::SnapShotType,
+    snapshot_batch_event::SnapShotBatchEvent,
+    stability::Stability,
+    stabilization::Stabilization,
+    story::{Story, StoryKind},
+    viewport::Viewport,
+};
 
 use anyhow::Error;
-use fantoccini::{Client, ClientBuilder};
-use futures_util::{future::join_all, stream::FuturesUnordered};
-use lazy_static::lazy_static;
+use futures_util::future::join_all;
+use tokio_util::sync::CancellationToken;
 
-lazy_static! {
-    static ref SELENIUM_PORT: String = env::var("SELENIUM_PORT").unwrap();
-    static ref SELENIUM_HOST: String = env::var("SELENIUM_HOST").unwrap();
-    static ref SELENIUM_URL: String = format!(
-        "http://{}:{}",
-        SELENIUM_HOST.as_str(),
-        SELENIUM_PORT.as_str()
-    );
-}
+use super::{
+    batch_events::{self, SnapShotBatchEventSender},
+    capture_backend::{CapturePage, PageScreenshot},
+    compare_images, crop_image,
+    env_variables::EnvVariables,
+    mask_image,
+    readiness::{self, Readiness},
+    session_pool::SessionPool,
+    stabilization,
+    stitch_image::{self, Tile},
+};
+
+/// Matches the story root of Storybook 7 and later as well as of older versions
+const STORY_ROOT_SELECTOR: &str = "#storybook-root, #root";
+/// Matches the root docs pages render into, which Storybook shows instead of the story root
+const DOCS_ROOT_SELECTOR: &str = "#storybook-docs, #docs-root";
+
+/// Time given to a story to settle between two captures of the stability check
+const STABILITY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time given to lazily loaded content to show up after scrolling, before a full page tile is taken
+const SCROLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Width and height of the viewport and height of the page, in css pixels
+const PAGE_METRICS_SCRIPT: &str = r"
+    return [window.innerWidth, window.innerHeight, document.documentElement.scrollHeight];
+";
+
+/// Scrolls down to `arguments[0]` and returns where the page ended up, as browsers stop at the bottom
+const SCROLL_SCRIPT: &str = r"
+    window.scrollTo(0, arguments[0]);
+    return window.scrollY;
+";
+
+/// Rectangles of the elements matching a selector, relative to the page
+const ELEMENT_RECTS_SCRIPT: &str = r"
+    return Array.prototype.map.call(document.querySelectorAll(arguments[0]), function (element) {
+        var rect = element.getBoundingClientRect();
+        return [rect.left + window.scrollX, rect.top + window.scrollY, rect.width, rect.height];
+    });
+";
 
 #[derive(Clone)]
 pub struct ScreenShotParams {
     pub url: String,
     pub id: String,
     pub image_type: SnapShotType,
-    pub name: String
+    pub name: String,
+    pub viewport: Option<Viewport>,
+    pub browser: Browser,
+    pub globals: Globals,
+    /// Element put in a state such as hover before the story is captured
+    pub interaction: Option<Interaction>,
+    /// Css media the story is rendered in, such as a dark color scheme
+    pub media: Option<MediaEmulation>,
+    /// Device the browser emulates, which sets the viewport as well
+    pub device: Option<Device>,
+    /// Story of the index the image is captured of
+    pub story: Story,
+}
+
+/// How every story of a batch is captured
+#[derive(Debug, Clone)]
+pub struct CaptureSettings {
+    pub readiness: Readiness,
+    pub crop: Option<Crop>,
+    pub full_page: Option<FullPage>,
+    pub masks: Vec<Mask>,
+    pub stabilization: Stabilization,
+    pub stability: Option<Stability>,
+}
+
+impl CaptureSettings {
+    pub fn new(options: &CaptureOptions, env_variables: &EnvVariables) -> Self {
+        Self {
+            readiness: Readiness::new(options, env_variables),
+            crop: options.crop.clone(),
+            full_page: options.full_page.clone(),
+            masks: options.masks.clone(),
+            stabilization: options.stabilization.clone(),
+            stability: options.stability.clone(),
+        }
+    }
 }
 
 pub async fn capture_screenshots(
-    urls: &Vec<ScreenShotParams>,
+    urls: &[ScreenShotParams],
+    settings: &CaptureSettings,
+    session_pool: &SessionPool,
+    events: &SnapShotBatchEventSender,
+    cancellation: &CancellationToken,
 ) -> Result<Vec<Result<RawImage, Error>>, Error> {
-    let handles = FuturesUnordered::new();
-    let mut raw_images: Vec<Result<RawImage, Error>> = vec![];
+    // Every story waits for a session of its own, so the pool decides how many run at once
+    let handles: Vec<tokio::task::JoinHandle<Option<Result<RawImage, Error>>>> = urls
+        .iter()
+        .map(|param| {
+            tokio::spawn(take_screenshot_with_session(
+                param.clone(),
+                settings.clone(),
+                session_pool.clone(),
+                events.clone(),
+                cancellation.clone(),
+            ))
+        })
+        .collect();
 
-    let chunk_size = urls.len() / available_parallelism().unwrap().get();
-
-    for chunk in urls.chunks(chunk_size) {
-        let chunk = chunk.to_vec();
-        let future: tokio::task::JoinHandle<Result<Vec<Result<RawImage, Error>>, Error>> =
-            tokio::spawn(take_screenshots(chunk));
-        handles.push(future);
-    }
+    let mut raw_images: Vec<Result<RawImage, Error>> = vec![];
 
     for handle in join_all(handles).await {
-        let result = handle?;
+        raw_images.extend(handle?);
+    }
 
-        raw_images.extend(result?);
+    if cancellation.is_cancelled() {
+        return Err(Error::msg("Capturing screen shots was cancelled"));
     }
 
     Ok(raw_images)
 }
 
-async fn take_screenshots(
-    params: Vec<ScreenShotParams>,
-) -> Result<Vec<Result<RawImage, Error>>, Error> {
-    let mut raw_images: Vec<Result<RawImage, Error>> = vec![];
-    tracing::debug!("Connecting to selenium");
-    let client: Client = connect().await.map_err(|err| {
-        tracing::error!("Unable to connect to selenium{}", err.to_string());
-        err
-    })?;
+/// Returns `None` when the batch was cancelled before the story was captured
+async fn take_screenshot_with_session(
+    param: ScreenShotParams,
+    settings: CaptureSettings,
+    session_pool: SessionPool,
+    events: SnapShotBatchEventSender,
+    cancellation: CancellationToken,
+) -> Option<Result<RawImage, Error>> {
+    let name = param.name.clone();
+    let image_type = param.image_type;
 
-    for param in params.into_iter() {
-        let screen_shot = capture_screenshot_from_url(&client, param).await;
+    let session = tokio::select! {
+        _ = cancellation.cancelled() => return None,
+        session = session_pool.acquire(param.browser, param.device.as_ref()) => session,
+    };
 
-        match screen_shot {
-            Ok(screen_shot) => {
-                raw_images.push(Ok(screen_shot));
-            }
-            Err(e) => {
-                tracing::error!("Error capturing screenshot: {}", e);
-                raw_images.push(Err(e));
+    let screen_shot = match session {
+        Ok(mut session) => {
+            session.record_navigation();
+
+            let captured = tokio::select! {
+                _ = cancellation.cancelled() => None,
+                screen_shot = capture_screenshot_from_url(session.page(), param, &settings) => {
+                    Some(screen_shot)
+                }
+            };
+
+            match captured {
+                Some(screen_shot) => screen_shot,
+                None => {
+                    // The page was left mid-story, so it must not capture the next one
+                    session.poison();
+                    return None;
+                }
             }
         }
+        Err(err) => Err(err),
+    };
+
+    match &screen_shot {
+        Ok(_) => {
+            batch_events::emit(&events, SnapShotBatchEvent::Captured { name, image_type });
+        }
+        Err(e) => {
+            tracing::error!("Error capturing screenshot: {}", e);
+            batch_events::emit(
+                &events,
+                SnapShotBatchEvent::CaptureFailed {
+                    name,
+                    image_type,
+                    error: e.to_string(),
+                },
+            );
+        }
     }
 
-    Ok(raw_images)
+    Some(screen_shot)
 }
 
 async fn capture_screenshot_from_url(
-    client: &Client,
+    page: &dyn CapturePage,
     param: ScreenShotParams,
+    settings: &CaptureSettings,
 ) -> Result<RawImage, Error> {
-    const TIME_OUT: Duration = std::time::Duration::from_secs(5);
-    const INTERVAL: Duration = std::time::Duration::from_millis(500);
+    match &param.viewport {
+        Some(viewport) => {
+            page.set_viewport(viewport.width, viewport.height)
+                .await
+                .map_err(|err| {
+                    tracing::error!("Unable to set viewport {}\n{}", viewport.name, err);
+                    err
+                })?;
+        }
+        // Sessions are reused, so a previous story may have left another size behind
+        None => page.reset_viewport().await?,
+    }
 
-    client.goto(&param.url).await.map_err(|err| {
+    if let Some(media) = param.media {
+        page.emulate_media(Some(media)).await.map_err(|err| {
+            tracing::error!("Unable to emulate {} media\n{}", media, err);
+            err
+        })?;
+    }
+
+    let captured = render_and_capture(page, &param, settings).await;
+
+    // Sessions are reused, so the next story must be rendered as a screen again
+    if param.media.is_some() {
+        if let Err(err) = page.emulate_media(None).await {
+            tracing::warn!("Unable to reset emulated media in {}\n{}", &param.url, err);
+        }
+    }
+
+    let (capture, unstable) = captured?;
+
+    tracing::debug!("Captured sceen shot for {}", &param.url);
+
+    Ok(RawImage {
+        raw_image: capture.screenshot,
+        width: capture.width,
+        height: capture.height,
+        image_name: param.name,
+        image_type: param.image_type,
+        viewport: param.viewport.map(|viewport| viewport.name),
+        browser: param.browser,
+        globals: param.globals,
+        device_pixel_ratio: capture.device_pixel_ratio,
+        crop: capture.crop,
+        masks: capture.masks,
+        story: Some(param.story),
+        unstable,
+    })
+}
+
+/// Loads the story and captures it once it is ready, returning whether it never stabilized
+async fn render_and_capture(
+    page: &dyn CapturePage,
+    param: &ScreenShotParams,
+    settings: &CaptureSettings,
+) -> Result<(Capture, bool), Error> {
+    page.goto(&param.url).await.map_err(|err| {
         tracing::error!(
             "Unable to go to URL {} to take screen shot\n{}",
             &param.url,
@@ -92,54 +271,550 @@ async fn capture_screenshot_from_url(
         err
     })?;
 
-    client
-        .wait()
-        .at_most(TIME_OUT)
-        .every(INTERVAL)
-        .for_element(fantoccini::Locator::XPath("/html/body/div[5]/*"))
+    wait_until_ready(page, param, settings)
         .await
         .map_err(|err| {
-            tracing::error!("Unable to find component\n{}", err.to_string());
+            tracing::error!("Story {} was not ready\n{}", &param.url, err.to_string());
             err
         })?;
 
-    let element = client
-        .find(fantoccini::Locator::XPath("/html"))
-        .await
-        .unwrap();
+    if settings.stabilization.is_enabled() {
+        let rendered_again = stabilization::stabilize(page, &settings.stabilization)
+            .await
+            .map_err(|err| {
+                tracing::error!("Unable to stabilize story {}\n{}", &param.url, err);
+                err
+            })?;
 
-    let dimensions = element.rectangle().await?;
-    let screenshot = element.screenshot().await?;
+        if rendered_again {
+            wait_until_ready(page, param, settings).await?;
+        }
+    }
 
-    tracing::debug!("Captured sceen shot for {}", &param.url);
+    let captured = interact_and_capture(page, param, settings).await;
 
-    Ok(RawImage {
-        raw_image: screenshot,
-        width: dimensions.2,
-        height: dimensions.3,
-        image_name: param.name,
-        image_type: param.image_type,
+    // Sessions are reused, so the next story must not start out hovered or pressed
+    if param.interaction.is_some() {
+        if let Err(err) = page.release_interaction().await {
+            tracing::warn!("Unable to release interaction in {}\n{}", &param.url, err);
+        }
+    }
+
+    captured
+}
+
+async fn wait_until_ready(
+    page: &dyn CapturePage,
+    param: &ScreenShotParams,
+    settings: &CaptureSettings,
+) -> Result<(), Error> {
+    match param.story.kind {
+        StoryKind::Story => readiness::wait_until_ready(page, &settings.readiness).await,
+        StoryKind::Docs => readiness::wait_until_docs_ready(page, &settings.readiness).await,
+    }
+}
+
+/// Captures the story in its interaction state, returning whether it never stabilized
+async fn interact_and_capture(
+    page: &dyn CapturePage,
+    param: &ScreenShotParams,
+    settings: &CaptureSettings,
+) -> Result<(Capture, bool), Error> {
+    if let Some(interaction) = &param.interaction {
+        page.interact(&interaction.selector, interaction.state)
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    "Unable to {} {} in story {}\n{}",
+                    interaction.state,
+                    interaction.selector,
+                    &param.url,
+                    err
+                );
+                err
+            })?;
+    }
+
+    let mut capture = take_screenshot(page, settings, param).await?;
+    let mut unstable = false;
+
+    if let Some(stability) = &settings.stability {
+        unstable = true;
+
+        for _ in 1..stability.max_attempts {
+            tokio::time::sleep(STABILITY_INTERVAL).await;
+
+            let next_capture = take_screenshot(page, settings, param).await?;
+            let is_stable =
+                compare_images::is_identical(&capture.screenshot, &next_capture.screenshot)?;
+
+            capture = next_capture;
+
+            if is_stable {
+                unstable = false;
+                break;
+            }
+        }
+
+        if unstable {
+            tracing::warn!(
+                "Story {} did not stabilize after {} captures",
+                &param.url,
+                stability.max_attempts
+            );
+        }
+    }
+
+    Ok((capture, unstable))
+}
+
+/// Screenshot of the story as it is stored, masked and cropped
+struct Capture {
+    screenshot: Vec<u8>,
+    width: f64,
+    height: f64,
+    crop: Option<CropRect>,
+    masks: Vec<MaskedRegion>,
+    device_pixel_ratio: f64,
+}
+
+async fn take_screenshot(
+    page: &dyn CapturePage,
+    settings: &CaptureSettings,
+    param: &ScreenShotParams,
+) -> Result<Capture, Error> {
+    // Elements are measured before the screenshot, which may scroll the page or resize it
+    let mut masks = find_masked_regions(page, settings, &param.id).await?;
+    let crop_rect = match &settings.crop {
+        Some(crop) => Some(find_crop_rect(page, crop, param.story.kind).await?),
+        None => None,
+    };
+
+    let page_screenshot = match &settings.full_page {
+        Some(full_page) => capture_full_page(page, full_page).await?,
+        None => page.screenshot().await?,
+    };
+    let mut screenshot = page_screenshot.image;
+
+    let device_pixel_ratio = measure_device_pixel_ratio(
+        &screenshot,
+        page_screenshot.width,
+        param
+            .device
+            .as_ref()
+            .map_or(1.0, |device| device.device_pixel_ratio),
+    )?;
+
+    if !masks.is_empty() {
+        screenshot = mask_image::mask_screenshot(&screenshot, page_screenshot.width, &masks)?;
+    }
+
+    let (screenshot, width, height, crop) = match (&settings.crop, crop_rect) {
+        (Some(crop), Some(element)) => {
+            let (screenshot, rect) = crop_image::crop_screenshot(
+                &screenshot,
+                page_screenshot.width,
+                page_screenshot.height,
+                element,
+                crop.padding,
+            )?;
+            masks = mask_image::regions_within(masks, &rect);
+            (screenshot, rect.width, rect.height, Some(rect))
+        }
+        _ => (
+            screenshot,
+            page_screenshot.width,
+            page_screenshot.height,
+            None,
+        ),
+    };
+
+    Ok(Capture {
+        screenshot,
+        width,
+        height,
+        crop,
+        masks,
+        device_pixel_ratio,
     })
 }
 
-async fn connect() -> Result<Client, Error> {
-    let mut caps: serde_json::Map<String, serde_json::Value> = serde_json::map::Map::new();
-    let args = serde_json::json!([
-        "--headless",
-        "--disable-gpu",
-        "--no-sandbox",
-        "--disable-dev-shm-usage"
-    ]);
-    let opts = serde_json::json!({
-        "args": args,
+/// Device pixel ratio of a screenshot of a page `page_width` css pixels wide. It is measured
+/// rather than taken from the device, as browsers may ignore parts of the emulation
+fn measure_device_pixel_ratio(
+    screenshot: &[u8],
+    page_width: f64,
+    expected: f64,
+) -> Result<f64, Error> {
+    if page_width <= 0.0 {
+        return Ok(expected);
+    }
+
+    let measured = image::load_from_memory(screenshot)?.width() as f64 / page_width;
+
+    // Images are made of whole pixels, so ratios such as 2.625 only come out approximately
+    if (measured - expected).abs() <= 1.0 / page_width {
+        Ok(expected)
+    } else {
+        Ok((measured * 100.0).round() / 100.0)
+    }
+}
+
+/// Scrolls through the page a viewport at a time and stitches the screenshots together,
+/// as element screenshots are cut off at the viewport by some drivers
+async fn capture_full_page(
+    page: &dyn CapturePage,
+    full_page: &FullPage,
+) -> Result<PageScreenshot, Error> {
+    let metrics = page.execute(PAGE_METRICS_SCRIPT, vec![]).await?;
+    let [width, viewport_height, page_height]: [f64; 3] = serde_json::from_value(metrics)?;
+
+    if viewport_height <= 0.0 {
+        return Err(Error::msg(
+            "Unable to scroll through a page without a viewport",
+        ));
+    }
+
+    let height = page_height.min(full_page.max_height as f64);
+
+    if height < page_height {
+        tracing::warn!(
+            "Page is {}px tall, capturing only the first {}px",
+            page_height,
+            height
+        );
+    }
+
+    let mut tiles = vec![];
+    let mut scroll_y = 0.0;
+
+    while scroll_y < height {
+        let scrolled_to = page
+            .execute(SCROLL_SCRIPT, vec![serde_json::json!(scroll_y)])
+            .await?
+            .as_f64()
+            .unwrap_or(scroll_y);
+
+        tokio::time::sleep(SCROLL_INTERVAL).await;
+
+        tiles.push(Tile {
+            image: page.viewport_screenshot().await?,
+            scroll_y: scrolled_to,
+        });
+
+        scroll_y += viewport_height;
+    }
+
+    page.execute(SCROLL_SCRIPT, vec![serde_json::json!(0)])
+        .await?;
+
+    Ok(PageScreenshot {
+        image: stitch_image::stitch_tiles(&tiles, width, height)?,
+        width,
+        height,
+    })
+}
+
+async fn find_masked_regions(
+    page: &dyn CapturePage,
+    settings: &CaptureSettings,
+    story_id: &str,
+) -> Result<Vec<MaskedRegion>, Error> {
+    let mut regions = vec![];
+
+    for mask in settings
+        .masks
+        .iter()
+        .filter(|mask| mask.applies_to(story_id))
+    {
+        let rects = find_element_rects(page, &mask.selector)
+            .await
+            .map_err(|err| {
+                tracing::error!("Unable to find elements {} to mask\n{}", mask.selector, err);
+                err
+            })?;
+
+        regions.extend(rects.into_iter().map(|[x, y, width, height]| MaskedRegion {
+            selector: mask.selector.clone(),
+            x,
+            y,
+            width,
+            height,
+        }));
+    }
+
+    Ok(regions)
+}
+
+async fn find_crop_rect(
+    page: &dyn CapturePage,
+    crop: &Crop,
+    kind: StoryKind,
+) -> Result<CropRect, Error> {
+    let selector = crop.selector.as_deref().unwrap_or(match kind {
+        StoryKind::Story => STORY_ROOT_SELECTOR,
+        StoryKind::Docs => DOCS_ROOT_SELECTOR,
     });
 
-    caps.insert("goog:chromeOptions".to_string(), opts.clone());
+    let [x, y, width, height] = find_element_rects(page, selector)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            tracing::error!("Unable to find element {} to crop to", selector);
+            Error::msg(format!("No element matches {} to crop to", selector))
+        })?;
+
+    Ok(CropRect {
+        x,
+        y,
+        width,
+        height,
+    })
+}
 
-    let c = ClientBuilder::native()
-        .capabilities(caps)
-        .connect(&SELENIUM_URL)
+async fn find_element_rects(
+    page: &dyn CapturePage,
+    selector: &str,
+) -> Result<Vec<[f64; 4]>, Error> {
+    let rects = page
+        .execute(
+            ELEMENT_RECTS_SCRIPT,
+            vec![serde_json::Value::String(selector.to_string())],
+        )
         .await?;
 
-    Ok(c)
+    Ok(serde_json::from_value(rects)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        models::{interaction::InteractionState, readiness_strategy::ReadinessStrategy},
+        utils::capture_backend::{fake::FakeBackend, CaptureBackend},
+    };
+
+    fn screenshot_params(id: &str) -> ScreenShotParams {
+        ScreenShotParams {
+            url: format!("http://storybook/iframe.html?id={}", id),
+            id: id.to_string(),
+            image_type: SnapShotType::New,
+            name: id.to_string(),
+            viewport: Some(Viewport {
+                name: "mobile".to_string(),
+                width: 20,
+                height: 10,
+            }),
+            browser: Browser::Firefox,
+            globals: Globals::new(),
+            interaction: None,
+            media: None,
+            device: None,
+            story: Story {
+                id: id.to_string(),
+                title: "Form".to_string(),
+                name: "Default".to_string(),
+                import_path: None,
+                tags: vec![],
+                kind: StoryKind::Story,
+            },
+        }
+    }
+
+    /// Notes down whether elements were measured or the page was captured, in order
+    struct RecordingPage {
+        page: Box<dyn CapturePage>,
+        calls: std::sync::Mutex<Vec<&'static str>>,
+    }
+
+    #[async_trait::async_trait]
+    impl CapturePage for RecordingPage {
+        async fn set_viewport(&self, width: u32, height: u32) -> Result<(), Error> {
+            self.page.set_viewport(width, height).await
+        }
+
+        async fn reset_viewport(&self) -> Result<(), Error> {
+            self.page.reset_viewport().await
+        }
+
+        async fn emulate_media(&self, media: Option<MediaEmulation>) -> Result<(), Error> {
+            self.page.emulate_media(media).await
+        }
+
+        async fn goto(&self, url: &str) -> Result<(), Error> {
+            self.page.goto(url).await
+        }
+
+        async fn execute(&self, script: &str, args: Vec<Value>) -> Result<Value, Error> {
+            if script == ELEMENT_RECTS_SCRIPT {
+                self.calls.lock().unwrap().push("measure");
+            }
+            self.page.execute(script, args).await
+        }
+
+        async fn execute_async(&self, script: &str, args: Vec<Value>) -> Result<Value, Error> {
+            self.page.execute_async(script, args).await
+        }
+
+        async fn interact(&self, selector: &str, state: InteractionState) -> Result<(), Error> {
+            self.page.interact(selector, state).await
+        }
+
+        async fn release_interaction(&self) -> Result<(), Error> {
+            self.page.release_interaction().await
+        }
+
+        async fn screenshot(&self) -> Result<PageScreenshot, Error> {
+            self.calls.lock().unwrap().push("screenshot");
+            self.page.screenshot().await
+        }
+
+        async fn viewport_screenshot(&self) -> Result<Vec<u8>, Error> {
+            self.page.viewport_screenshot().await
+        }
+
+        async fn is_healthy(&self) -> bool {
+            self.page.is_healthy().await
+        }
+
+        async fn close(self: Box<Self>) {}
+    }
+
+    #[tokio::test]
+    async fn test_capture_screenshots_with_fake_backend() {
+        let session_pool = SessionPool::new(Arc::new(FakeBackend::default()), 2, 1);
+        let (events, mut receiver) = tokio::sync::broadcast::channel(8);
+
+        let settings = CaptureSettings {
+            readiness: Readiness {
+                strategy: ReadinessStrategy::RenderComplete,
+                timeout: Duration::from_millis(100),
+            },
+            crop: None,
+            full_page: None,
+            masks: vec![],
+            stabilization: Stabilization::default(),
+            stability: Some(Stability { max_attempts: 2 }),
+        };
+
+        let params: Vec<ScreenShotParams> = ["button", "input"]
+            .into_iter()
+            .map(screenshot_params)
+            .collect();
+
+        let raw_images = capture_screenshots(
+            &params,
+            &settings,
+            &session_pool,
+            &events,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(raw_images.len(), 2);
+
+        for raw_image in raw_images {
+            let raw_image = raw_image.unwrap();
+
+            assert_eq!((raw_image.width, raw_image.height), (20.0, 10.0));
+            assert_eq!(raw_image.viewport.as_deref(), Some("mobile"));
+            assert_eq!(raw_image.browser, Browser::Firefox);
+            assert_eq!(raw_image.device_pixel_ratio, 1.0);
+            assert_eq!(
+                raw_image.story.map(|story| story.title).as_deref(),
+                Some("Form")
+            );
+            assert!(!raw_image.unstable);
+        }
+
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            SnapShotBatchEvent::Captured { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_take_screenshot_measures_elements_before_capturing() {
+        let backend = FakeBackend::default()
+            .with_script_result("querySelectorAll(arguments[0])", json!([[2, 2, 10, 5]]));
+        let page = RecordingPage {
+            page: backend.open_page(Browser::Chrome, None).await.unwrap(),
+            calls: std::sync::Mutex::new(vec![]),
+        };
+        page.set_viewport(20, 10).await.unwrap();
+
+        let settings = CaptureSettings {
+            readiness: Readiness {
+                strategy: ReadinessStrategy::RenderComplete,
+                timeout: Duration::from_millis(100),
+            },
+            crop: Some(Crop {
+                selector: None,
+                padding: 0,
+            }),
+            full_page: None,
+            masks: vec![Mask {
+                selector: ".date".to_string(),
+                stories: vec![],
+            }],
+            stabilization: Stabilization::default(),
+            stability: None,
+        };
+
+        let capture = take_screenshot(&page, &settings, &screenshot_params("button"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *page.calls.lock().unwrap(),
+            vec!["measure", "measure", "screenshot"]
+        );
+        assert_eq!((capture.width, capture.height), (10.0, 5.0));
+        assert_eq!(capture.masks.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_capture_full_page_stitches_up_to_max_height() {
+        // A 20 by 10 viewport on a page that is 35 tall
+        let backend = FakeBackend::default().with_script_result("innerHeight", json!([20, 10, 35]));
+        let session_pool = SessionPool::new(Arc::new(backend), 1, 1);
+        let session = session_pool.acquire(Browser::Chrome, None).await.unwrap();
+        session.page().set_viewport(20, 10).await.unwrap();
+
+        let screenshot = capture_full_page(session.page(), &FullPage { max_height: 25 })
+            .await
+            .unwrap();
+        let image = image::load_from_memory(&screenshot.image).unwrap();
+
+        assert_eq!((screenshot.width, screenshot.height), (20.0, 25.0));
+        assert_eq!((image.width(), image.height()), (20, 25));
+    }
+
+    #[test]
+    fn test_measure_device_pixel_ratio() {
+        let mut screenshot: Vec<u8> = Vec::new();
+        image::DynamicImage::new_rgba8(1082, 10)
+            .write_to(
+                &mut std::io::Cursor::new(&mut screenshot),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+
+        // 412 css pixels at 2.625 are 1081.5 pixels, which the browser rounds up
+        assert_eq!(
+            measure_device_pixel_ratio(&screenshot, 412.0, 2.625).unwrap(),
+            2.625
+        );
+        // A browser that ignored the emulated ratio of 3
+        assert_eq!(
+            measure_device_pixel_ratio(&screenshot, 541.0, 3.0).unwrap(),
+            2.0
+        );
+    }
 }
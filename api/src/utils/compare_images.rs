@@ -1,13 +1,23 @@
-use crate::models::{raw_image::RawImage, snapshot::SnapShotType};
+use crate::models::{
+    raw_image::RawImage,
+    snapshot::SnapShotType,
+    snapshot_batch_event::{CompareOutcome, SnapShotBatchEvent},
+};
 
 use futures_util::{future::join_all, stream::FuturesUnordered};
-use image::{DynamicImage, ImageFormat, Rgba};
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba};
 use serde::{Deserialize, Serialize};
 use std::io::Cursor;
 use std::thread::available_parallelism;
 use tokio::task::{self};
+use tokio_util::sync::CancellationToken;
 use utoipa::ToSchema;
 
+use super::{
+    batch_events::{self, SnapShotBatchEventSender},
+    mask_image,
+};
+
 const DIFF_RATIO_THRESHOLD: f64 = 0.0001;
 static RATE: f32 = 100.0 / 256.0;
 
@@ -16,6 +26,8 @@ pub struct CategorizedImages {
     created_images_paths: Vec<RawImage>,
     deleted_images_paths: Vec<RawImage>,
     diff_images_paths: Vec<(RawImage, RawImage)>,
+    unstable_images_paths: Vec<RawImage>,
+    incomparable_images_paths: Vec<(RawImage, String)>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
@@ -23,11 +35,16 @@ pub struct CompareImagesReturn {
     pub created_images_paths: Vec<RawImage>,
     pub deleted_images_paths: Vec<RawImage>,
     pub diff_images_paths: Vec<(RawImage, RawImage)>,
+    pub unstable_images_paths: Vec<RawImage>,
+    /// New images that were not compared with their old ones, with why
+    pub incomparable_images_paths: Vec<(RawImage, String)>,
 }
 
 pub async fn compare_images(
     image_paths_1: Vec<RawImage>,
     image_paths_2: Vec<RawImage>,
+    events: &SnapShotBatchEventSender,
+    cancellation: &CancellationToken,
 ) -> Result<CompareImagesReturn, anyhow::Error> {
     let num_threads = available_parallelism().unwrap().get();
 
@@ -35,11 +52,27 @@ pub async fn compare_images(
 
     let categorized_images = categorize_images(&image_paths_1, &image_paths_2);
 
+    for image in categorized_images.unstable_images_paths.iter() {
+        batch_events::emit(
+            events,
+            SnapShotBatchEvent::compared(image.image_name.clone(), CompareOutcome::Unstable),
+        );
+    }
+
+    for (image, reason) in categorized_images.incomparable_images_paths.iter() {
+        batch_events::emit(
+            events,
+            SnapShotBatchEvent::compare_failed(image.image_name.clone(), reason.clone()),
+        );
+    }
+
     if categorized_images.diff_images_paths.is_empty() {
         return Ok(CompareImagesReturn {
             created_images_paths: categorized_images.created_images_paths.clone(),
             deleted_images_paths: categorized_images.deleted_images_paths.clone(),
             diff_images_paths: vec![],
+            unstable_images_paths: categorized_images.unstable_images_paths.clone(),
+            incomparable_images_paths: categorized_images.incomparable_images_paths.clone(),
         });
     }
 
@@ -50,27 +83,40 @@ pub async fn compare_images(
 
     for chunk in categorized_images.diff_images_paths.chunks(chunk_size) {
         let chunk: Vec<(RawImage, RawImage)> = chunk.to_vec();
-        handles.push(task::spawn(compare_image_chunk(chunk)));
+        handles.push(task::spawn(compare_image_chunk(
+            chunk,
+            events.clone(),
+            cancellation.clone(),
+        )));
     }
 
-    let diff_images = join_all(handles.into_iter())
-        .await
-        .into_iter()
-        .map(|handle| handle.unwrap())
-        .flat_map(|arr| arr.unwrap())
-        .collect::<Vec<(RawImage, RawImage)>>();
+    let mut diff_images: Vec<(RawImage, RawImage)> = vec![];
+
+    for handle in join_all(handles.into_iter()).await {
+        diff_images.extend(handle??);
+    }
 
     Ok(CompareImagesReturn {
         created_images_paths: categorized_images.created_images_paths.clone(),
         deleted_images_paths: categorized_images.deleted_images_paths.clone(),
         diff_images_paths: diff_images,
+        unstable_images_paths: categorized_images.unstable_images_paths.clone(),
+        incomparable_images_paths: categorized_images.incomparable_images_paths.clone(),
     })
 }
 
 async fn compare_image_chunk(
     chunk: Vec<(RawImage, RawImage)>,
+    events: SnapShotBatchEventSender,
+    cancellation: CancellationToken,
 ) -> Result<Vec<(RawImage, RawImage)>, anyhow::Error> {
-    let result = chunk.into_iter().map(|(raw_image_1, raw_image_2)| {
+    // Stop picking up new pairs once the batch is cancelled
+    let pending = chunk
+        .into_iter()
+        .take_while(|_| !cancellation.is_cancelled());
+
+    let result = pending.map(|(raw_image_1, raw_image_2)| {
+        let name = raw_image_1.image_name.clone();
         let image_result: Result<Option<(RawImage, RawImage)>, anyhow::Error> = (|| {
             let mut image_1 = image::load_from_memory(&raw_image_1.raw_image).map_err(|_| {
                 anyhow::Error::msg(format!("Failed to open image: {}", &raw_image_1.image_name))
@@ -80,6 +126,11 @@ async fn compare_image_chunk(
                 anyhow::Error::msg(format!("Failed to open image: {}", &raw_image_2.image_name))
             })?;
 
+            // Mask what either capture masked so that a masked element that moved is ignored too
+            let masks = mask_image::merge_regions(&raw_image_1.masks, &raw_image_2.masks);
+            mask_image::mask_regions(&mut image_1, raw_image_1.width, &masks);
+            mask_image::mask_regions(&mut image_2, raw_image_2.width, &masks);
+
             let ratio = diff_img::calculate_diff_ratio(image_1.clone(), image_2.clone());
 
             if ratio < DIFF_RATIO_THRESHOLD {
@@ -120,6 +171,14 @@ async fn compare_image_chunk(
                     image_type: SnapShotType::ColorDiff,
                     height: color_diff.height() as f64,
                     width: color_diff.width() as f64,
+                    viewport: raw_image_1.viewport.clone(),
+                    browser: raw_image_1.browser,
+                    globals: raw_image_1.globals.clone(),
+                    device_pixel_ratio: raw_image_1.device_pixel_ratio,
+                    crop: raw_image_1.crop,
+                    masks: masks.clone(),
+                    story: raw_image_1.story.clone(),
+                    unstable: false,
                 },
                 RawImage {
                     raw_image: image_to_vec_u8(lcs_diff.clone(), ImageFormat::Png),
@@ -127,10 +186,25 @@ async fn compare_image_chunk(
                     image_type: SnapShotType::LcsDiff,
                     height: lcs_diff.height() as f64,
                     width: lcs_diff.width() as f64,
+                    viewport: raw_image_1.viewport,
+                    browser: raw_image_1.browser,
+                    globals: raw_image_1.globals,
+                    device_pixel_ratio: raw_image_1.device_pixel_ratio,
+                    crop: raw_image_1.crop,
+                    masks,
+                    story: raw_image_1.story,
+                    unstable: false,
                 },
             )))
         })();
 
+        let event = match &image_result {
+            Ok(Some(_)) => SnapShotBatchEvent::compared(name, CompareOutcome::DiffFound),
+            Ok(None) => SnapShotBatchEvent::compared(name, CompareOutcome::Unchanged),
+            Err(e) => SnapShotBatchEvent::compare_failed(name, e.to_string()),
+        };
+        batch_events::emit(&events, event);
+
         image_result
     });
 
@@ -145,6 +219,10 @@ async fn compare_image_chunk(
         })
         .collect::<Vec<(RawImage, RawImage)>>();
 
+    if cancellation.is_cancelled() {
+        return Err(anyhow::Error::msg("Comparing images was cancelled"));
+    }
+
     Ok(filtered_result)
 }
 
@@ -155,6 +233,8 @@ fn categorize_images(
     let mut created_images: Vec<RawImage> = Vec::new();
     let mut deleted_images: Vec<RawImage> = Vec::new();
     let mut diff_images: Vec<(RawImage, RawImage)> = Vec::new();
+    let mut unstable_images: Vec<RawImage> = Vec::new();
+    let mut incomparable_images: Vec<(RawImage, String)> = Vec::new();
 
     image_paths_1
         .clone()
@@ -165,6 +245,27 @@ fn categorize_images(
                 .find(|&r| r.image_name == image_1.image_name);
 
             match image_2 {
+                // A story that never rendered the same way twice can't be told apart from a change
+                Some(image_2) if image_1.unstable || image_2.unstable => {
+                    let mut unstable_image = if image_1.unstable {
+                        image_1.clone()
+                    } else {
+                        image_2.clone()
+                    };
+                    unstable_image.image_type = SnapShotType::Unstable;
+                    unstable_images.push(unstable_image);
+                }
+                // Every pixel differs at another density, which says nothing about the story
+                Some(image_2) if image_1.device_pixel_ratio != image_2.device_pixel_ratio => {
+                    let reason = format!(
+                        "Images of {} were captured at device pixel ratios {} and {} and cannot be compared",
+                        image_1.image_name, image_1.device_pixel_ratio, image_2.device_pixel_ratio
+                    );
+
+                    let mut incomparable_image = image_2.clone();
+                    incomparable_image.image_type = SnapShotType::Incomparable;
+                    incomparable_images.push((incomparable_image, reason));
+                }
                 Some(image_2) => {
                     let mut image_1 = image_1.clone();
                     image_1.image_type = SnapShotType::Old;
@@ -197,9 +298,24 @@ fn categorize_images(
         created_images_paths: created_images,
         deleted_images_paths: deleted_images,
         diff_images_paths: diff_images,
+        unstable_images_paths: unstable_images,
+        incomparable_images_paths: incomparable_images,
     }
 }
 
+/// Whether two encoded images have exactly the same pixels
+pub fn is_identical(image_1: &[u8], image_2: &[u8]) -> Result<bool, anyhow::Error> {
+    if image_1 == image_2 {
+        return Ok(true);
+    }
+
+    let image_1 = image::load_from_memory(image_1)?;
+    let image_2 = image::load_from_memory(image_2)?;
+
+    Ok(image_1.dimensions() == image_2.dimensions()
+        && image_1.to_rgba8().as_raw() == image_2.to_rgba8().as_raw())
+}
+
 fn image_to_vec_u8(image: DynamicImage, format: ImageFormat) -> Vec<u8> {
     let mut buffer = Cursor::new(Vec::new());
     image.write_to(&mut buffer, format).unwrap();
@@ -209,165 +325,223 @@ fn image_to_vec_u8(image: DynamicImage, format: ImageFormat) -> Vec<u8> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+    use crate::models::{browser::Browser, globals::Globals};
+
+    /// Image of a story captured in Chrome at a device pixel ratio of 1, without a
+    /// viewport, globals, crop, masks or story
+    fn raw_image(image_name: &str, image_type: SnapShotType, raw_image: Vec<u8>) -> RawImage {
+        RawImage {
+            raw_image,
+            image_name: image_name.to_string(),
+            image_type,
+            height: 0.0,
+            width: 0.0,
+            viewport: None,
+            browser: Browser::Chrome,
+            globals: Globals::new(),
+            device_pixel_ratio: 1.0,
+            crop: None,
+            masks: vec![],
+            story: None,
+            unstable: false,
+        }
+    }
+
     #[tokio::test]
     async fn test_compare_images_diff() {
         let image_1 = image::open("tests/images/image1.png").unwrap();
         let image_2 = image::open("tests/images/image2.png").unwrap();
 
-        let images_1 = vec![RawImage {
-            raw_image: image_to_vec_u8(image_1, ImageFormat::Png),
-            image_name: "image1.png".to_string(),
-            image_type: SnapShotType::Old,
-            height: 0.0,
-            width: 0.0,
-        }];
+        let images_1 = vec![raw_image(
+            "image1.png",
+            SnapShotType::Old,
+            image_to_vec_u8(image_1, ImageFormat::Png),
+        )];
 
-        let images_2 = vec![RawImage {
-            raw_image: image_to_vec_u8(image_2, ImageFormat::Png),
-            image_name: "image1.png".to_string(),
-            image_type: SnapShotType::New,
-            height: 0.0,
-            width: 0.0,
-        }];
+        let images_2 = vec![raw_image(
+            "image1.png",
+            SnapShotType::New,
+            image_to_vec_u8(image_2, ImageFormat::Png),
+        )];
 
-        let res = compare_images(images_1, images_2).await.unwrap();
+        let (events, _) = tokio::sync::broadcast::channel(16);
+        let res = compare_images(images_1, images_2, &events, &CancellationToken::new())
+            .await
+            .unwrap();
 
         assert_eq!(res.created_images_paths.len(), 0);
         assert_eq!(res.deleted_images_paths.len(), 0);
         assert_eq!(res.diff_images_paths.len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_compare_images_refuses_different_device_pixel_ratios() {
+        let image = |device_pixel_ratio: f64, image_type: SnapShotType| RawImage {
+            device_pixel_ratio,
+            ..raw_image(
+                "image1.png",
+                image_type,
+                image_to_vec_u8(
+                    image::open("tests/images/image1.png").unwrap(),
+                    ImageFormat::Png,
+                ),
+            )
+        };
+
+        let (events, mut receiver) = tokio::sync::broadcast::channel(16);
+        let res = compare_images(
+            vec![image(1.0, SnapShotType::Old)],
+            vec![image(2.0, SnapShotType::New)],
+            &events,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let reason = "Images of image1.png were captured at device pixel ratios 1 and 2 and cannot be compared";
+
+        assert_eq!(res.diff_images_paths.len(), 0);
+        assert_eq!(
+            res.incomparable_images_paths,
+            vec![(
+                RawImage {
+                    image_type: SnapShotType::Incomparable,
+                    ..image(2.0, SnapShotType::New)
+                },
+                reason.to_string()
+            )]
+        );
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            SnapShotBatchEvent::compare_failed("image1.png".to_string(), reason.to_string())
+        );
+    }
+
     #[tokio::test]
     async fn test_compare_images_no_diff() {
         let image_1 = image::open("tests/images/image1.png").unwrap();
         let image_2 = image::open("tests/images/image2.png").unwrap();
 
-        let images_1 = vec![RawImage {
-            raw_image: image_to_vec_u8(image_1, ImageFormat::Png),
-            image_name: "image1.png".to_string(),
-            image_type: SnapShotType::Old,
-            height: 0.0,
-            width: 0.0,
-        }];
+        let images_1 = vec![raw_image(
+            "image1.png",
+            SnapShotType::Old,
+            image_to_vec_u8(image_1, ImageFormat::Png),
+        )];
 
-        let images_2 = vec![RawImage {
-            raw_image: image_to_vec_u8(image_2, ImageFormat::Png),
-            image_name: "image2.png".to_string(),
-            image_type: SnapShotType::New,
-            height: 0.0,
-            width: 0.0,
-        }];
+        let images_2 = vec![raw_image(
+            "image2.png",
+            SnapShotType::New,
+            image_to_vec_u8(image_2, ImageFormat::Png),
+        )];
 
-        let res = compare_images(images_1, images_2).await.unwrap();
+        let (events, _) = tokio::sync::broadcast::channel(16);
+        let res = compare_images(images_1, images_2, &events, &CancellationToken::new())
+            .await
+            .unwrap();
 
         assert_eq!(res.created_images_paths.len(), 1);
         assert_eq!(res.deleted_images_paths.len(), 1);
         assert_eq!(res.diff_images_paths.len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_compare_images_cancelled() {
+        let image_1 = image::open("tests/images/image1.png").unwrap();
+        let image_2 = image::open("tests/images/image2.png").unwrap();
+
+        let images_1 = vec![raw_image(
+            "image1.png",
+            SnapShotType::Old,
+            image_to_vec_u8(image_1, ImageFormat::Png),
+        )];
+
+        let images_2 = vec![raw_image(
+            "image1.png",
+            SnapShotType::New,
+            image_to_vec_u8(image_2, ImageFormat::Png),
+        )];
+
+        let (events, _) = tokio::sync::broadcast::channel(16);
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+
+        let res = compare_images(images_1, images_2, &events, &cancellation).await;
+
+        assert!(res.is_err());
+    }
+
     #[test]
     fn test_categorize_images() {
         let image_1 = vec![
-            RawImage {
-                raw_image: vec![],
-                image_name: "image1.jpg".to_string(),
-                image_type: SnapShotType::Old,
-                height: 0.0,
-                width: 0.0,
-            },
-            RawImage {
-                raw_image: vec![],
-                image_name: "image2.png".to_string(),
-                image_type: SnapShotType::Old,
-                height: 0.0,
-                width: 0.0,
-            },
-            RawImage {
-                raw_image: vec![],
-                image_name: "image3.gif".to_string(),
-                image_type: SnapShotType::Old,
-                height: 0.0,
-                width: 0.0,
-            },
+            raw_image("image1.jpg", SnapShotType::Old, vec![]),
+            raw_image("image2.png", SnapShotType::Old, vec![]),
+            raw_image("image3.gif", SnapShotType::Old, vec![]),
         ];
         let images_2: Vec<RawImage> = vec![
-            RawImage {
-                raw_image: vec![],
-                image_name: "image1.jpg".to_string(),
-                image_type: SnapShotType::New,
-                height: 0.0,
-                width: 0.0,
-            },
-            RawImage {
-                raw_image: vec![],
-                image_name: "image2.png".to_string(),
-                image_type: SnapShotType::New,
-                height: 0.0,
-                width: 0.0,
-            },
-            RawImage {
-                raw_image: vec![],
-                image_name: "otherpath/image4.bmp".to_string(),
-                image_type: SnapShotType::New,
-                height: 0.0,
-                width: 0.0,
-            },
+            raw_image("image1.jpg", SnapShotType::New, vec![]),
+            raw_image("image2.png", SnapShotType::New, vec![]),
+            raw_image("otherpath/image4.bmp", SnapShotType::New, vec![]),
         ];
 
         let expected_result = CategorizedImages {
-            created_images_paths: vec![RawImage {
-                raw_image: vec![],
-                image_name: "image3.gif".to_string(),
-                image_type: SnapShotType::Create,
-                height: 0.0,
-                width: 0.0,
-            }],
-            deleted_images_paths: vec![RawImage {
-                raw_image: vec![],
-                image_name: "otherpath/image4.bmp".to_string(),
-                image_type: SnapShotType::Deleted,
-                height: 0.0,
-                width: 0.0,
-            }],
+            created_images_paths: vec![raw_image("image3.gif", SnapShotType::Create, vec![])],
+            deleted_images_paths: vec![raw_image(
+                "otherpath/image4.bmp",
+                SnapShotType::Deleted,
+                vec![],
+            )],
             diff_images_paths: vec![
                 (
-                    RawImage {
-                        raw_image: vec![],
-                        image_name: "image1.jpg".to_string(),
-                        image_type: SnapShotType::Old,
-                        height: 0.0,
-                        width: 0.0,
-                    },
-                    RawImage {
-                        raw_image: vec![],
-                        image_name: "image1.jpg".to_string(),
-                        image_type: SnapShotType::New,
-                        height: 0.0,
-                        width: 0.0,
-                    },
+                    raw_image("image1.jpg", SnapShotType::Old, vec![]),
+                    raw_image("image1.jpg", SnapShotType::New, vec![]),
                 ),
                 (
-                    RawImage {
-                        raw_image: vec![],
-                        image_name: "image2.png".to_string(),
-                        image_type: SnapShotType::Old,
-                        height: 0.0,
-                        width: 0.0,
-                    },
-                    RawImage {
-                        raw_image: vec![],
-                        image_name: "image2.png".to_string(),
-                        image_type: SnapShotType::New,
-                        height: 0.0,
-                        width: 0.0,
-                    },
+                    raw_image("image2.png", SnapShotType::Old, vec![]),
+                    raw_image("image2.png", SnapShotType::New, vec![]),
                 ),
             ],
+            unstable_images_paths: vec![],
+            incomparable_images_paths: vec![],
         };
 
         let result = categorize_images(&image_1, &images_2);
 
         assert_eq!(result, expected_result);
     }
+
+    #[test]
+    fn test_is_identical() {
+        let image_1 = image_to_vec_u8(
+            image::open("tests/images/image1.png").unwrap(),
+            ImageFormat::Png,
+        );
+        let image_2 = image_to_vec_u8(
+            image::open("tests/images/image2.png").unwrap(),
+            ImageFormat::Png,
+        );
+
+        assert!(is_identical(&image_1, &image_1.clone()).unwrap());
+        assert!(!is_identical(&image_1, &image_2).unwrap());
+    }
+
+    #[test]
+    fn test_categorize_unstable_images() {
+        let image = raw_image("image1.png", SnapShotType::Old, vec![]);
+        let unstable_image = RawImage {
+            image_type: SnapShotType::New,
+            unstable: true,
+            ..image.clone()
+        };
+
+        let result = categorize_images(&vec![image], &vec![unstable_image.clone()]);
+
+        assert!(result.diff_images_paths.is_empty());
+        assert_eq!(
+            result.unstable_images_paths,
+            vec![RawImage {
+                image_type: SnapShotType::Unstable,
+                ..unstable_image
+            }]
+        );
+    }
 }
@@ -0,0 +1,139 @@
+use std::io::Cursor;
+
+use anyhow::Error;
+use image::ImageFormat;
+
+use crate::models::crop::CropRect;
+
+/// Crops a screenshot of the whole page to the given element rectangle, grown by
+/// the padding and clamped to the page. The screenshot may be larger than the page
+/// when the device pixel ratio is above one, so it is scaled accordingly.
+pub fn crop_screenshot(
+    screenshot: &[u8],
+    page_width: f64,
+    page_height: f64,
+    element: CropRect,
+    padding: u32,
+) -> Result<(Vec<u8>, CropRect), Error> {
+    let image = image::load_from_memory(screenshot)?;
+
+    let padding = padding as f64;
+    let left = (element.x - padding).max(0.0);
+    let top = (element.y - padding).max(0.0);
+    let right = (element.x + element.width + padding).min(page_width);
+    let bottom = (element.y + element.height + padding).min(page_height);
+
+    if right <= left || bottom <= top {
+        return Err(Error::msg("Element to crop to is outside of the page"));
+    }
+
+    let rect = CropRect {
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+    };
+
+    let scale = image.width() as f64 / page_width;
+
+    let cropped = image.crop_imm(
+        (rect.x * scale).round() as u32,
+        (rect.y * scale).round() as u32,
+        ((rect.width * scale).round() as u32).max(1),
+        ((rect.height * scale).round() as u32).max(1),
+    );
+
+    let mut bytes: Vec<u8> = Vec::new();
+    cropped.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+
+    Ok((bytes, rect))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screenshot(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        image::DynamicImage::new_rgba8(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+
+        bytes
+    }
+
+    #[test]
+    fn test_crop_screenshot_with_padding() {
+        let element = CropRect {
+            x: 10.0,
+            y: 20.0,
+            width: 30.0,
+            height: 40.0,
+        };
+
+        let (cropped, rect) =
+            crop_screenshot(&screenshot(200, 100), 200.0, 100.0, element, 5).unwrap();
+        let cropped = image::load_from_memory(&cropped).unwrap();
+
+        assert_eq!(
+            rect,
+            CropRect {
+                x: 5.0,
+                y: 15.0,
+                width: 40.0,
+                height: 50.0
+            }
+        );
+        assert_eq!((cropped.width(), cropped.height()), (40, 50));
+    }
+
+    #[test]
+    fn test_crop_screenshot_clamps_to_page() {
+        let element = CropRect {
+            x: 0.0,
+            y: 80.0,
+            width: 50.0,
+            height: 40.0,
+        };
+
+        let (_, rect) = crop_screenshot(&screenshot(200, 100), 200.0, 100.0, element, 10).unwrap();
+
+        assert_eq!(
+            rect,
+            CropRect {
+                x: 0.0,
+                y: 70.0,
+                width: 60.0,
+                height: 30.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_crop_screenshot_scales_to_pixel_ratio() {
+        let element = CropRect {
+            x: 10.0,
+            y: 10.0,
+            width: 20.0,
+            height: 20.0,
+        };
+
+        let (cropped, _) =
+            crop_screenshot(&screenshot(400, 200), 200.0, 100.0, element, 0).unwrap();
+        let cropped = image::load_from_memory(&cropped).unwrap();
+
+        assert_eq!((cropped.width(), cropped.height()), (40, 40));
+    }
+
+    #[test]
+    fn test_crop_screenshot_outside_of_page() {
+        let element = CropRect {
+            x: 300.0,
+            y: 0.0,
+            width: 20.0,
+            height: 20.0,
+        };
+
+        assert!(crop_screenshot(&screenshot(200, 100), 200.0, 100.0, element, 0).is_err());
+    }
+}
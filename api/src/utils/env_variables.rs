@@ -1,14 +1,30 @@
 use dotenv::dotenv;
 use std::env;
 
+use crate::models::readiness_strategy::ReadinessStrategy;
+
+use super::capture_backend::CaptureBackendKind;
+
+const DEFAULT_READINESS_TIMEOUT_MS: u64 = 5000;
+const DEFAULT_SELENIUM_MAX_SESSIONS: usize = 6;
+const DEFAULT_SELENIUM_SESSION_MAX_NAVIGATIONS: u32 = 50;
+
 #[derive(Clone)]
 pub struct EnvVariables {
     pub base_url: String,
     pub port: String,
     pub db_url: String,
-    pub selenium_port: String,
-    pub selenium_host: String,
+    pub capture_backend: CaptureBackendKind,
+    pub chrome_path: Option<String>,
+    /// Only set, and required, for the webdriver capture backend
+    pub selenium_port: Option<String>,
+    /// Only set, and required, for the webdriver capture backend
+    pub selenium_host: Option<String>,
+    pub selenium_max_sessions: usize,
+    pub selenium_session_max_navigations: u32,
     pub assets_folder: String,
+    pub readiness_strategy: ReadinessStrategy,
+    pub readiness_timeout_ms: u64,
 }
 
 impl EnvVariables {
@@ -30,14 +46,39 @@ impl EnvVariables {
             Err(_) => panic!("DATABASE_URL must be set"),
         };
 
-        let selenium_port = match env::var("SELENIUM_PORT") {
-            Ok(val) => val,
-            Err(_) => panic!("SELENIUM_PORT must be set"),
+        let capture_backend = match env::var("CAPTURE_BACKEND") {
+            Ok(val) => match val.parse() {
+                Ok(backend) => backend,
+                Err(err) => panic!("CAPTURE_BACKEND is invalid: {}", err),
+            },
+            Err(_) => CaptureBackendKind::default(),
         };
 
-        let selenium_host = match env::var("SELENIUM_HOST") {
-            Ok(val) => val,
-            Err(_) => panic!("SELENIUM_HOST must be set"),
+        let chrome_path = env::var("CHROME_PATH").ok();
+
+        let (selenium_host, selenium_port) = match selenium_variables(
+            capture_backend,
+            env::var("SELENIUM_HOST").ok(),
+            env::var("SELENIUM_PORT").ok(),
+        ) {
+            Ok(variables) => variables,
+            Err(err) => panic!("{}", err),
+        };
+
+        let selenium_max_sessions = match env::var("SELENIUM_MAX_SESSIONS") {
+            Ok(val) => match val.parse() {
+                Ok(max_sessions) if max_sessions > 0 => max_sessions,
+                _ => panic!("SELENIUM_MAX_SESSIONS must be a positive number"),
+            },
+            Err(_) => DEFAULT_SELENIUM_MAX_SESSIONS,
+        };
+
+        let selenium_session_max_navigations = match env::var("SELENIUM_SESSION_MAX_NAVIGATIONS") {
+            Ok(val) => match val.parse() {
+                Ok(max_navigations) if max_navigations > 0 => max_navigations,
+                _ => panic!("SELENIUM_SESSION_MAX_NAVIGATIONS must be a positive number"),
+            },
+            Err(_) => DEFAULT_SELENIUM_SESSION_MAX_NAVIGATIONS,
         };
 
         let assets_folder = match env::var("ASSETS_FOLDER") {
@@ -45,17 +86,56 @@ impl EnvVariables {
             Err(_) => panic!("ASSETS_FOLDER must be set"),
         };
 
+        let readiness_strategy = match env::var("READINESS_STRATEGY") {
+            Ok(val) => match val.parse() {
+                Ok(strategy) => strategy,
+                Err(err) => panic!("READINESS_STRATEGY is invalid: {}", err),
+            },
+            Err(_) => ReadinessStrategy::default(),
+        };
+
+        let readiness_timeout_ms = match env::var("READINESS_TIMEOUT_MS") {
+            Ok(val) => match val.parse() {
+                Ok(timeout) => timeout,
+                Err(_) => panic!("READINESS_TIMEOUT_MS must be a number"),
+            },
+            Err(_) => DEFAULT_READINESS_TIMEOUT_MS,
+        };
+
         Self {
             assets_folder,
             base_url,
             port,
             db_url,
+            capture_backend,
+            chrome_path,
             selenium_port,
             selenium_host,
+            selenium_max_sessions,
+            selenium_session_max_navigations,
+            readiness_strategy,
+            readiness_timeout_ms,
         }
     }
 }
 
+/// Host and port of the Selenium node, which only the webdriver backend talks to
+fn selenium_variables(
+    capture_backend: CaptureBackendKind,
+    selenium_host: Option<String>,
+    selenium_port: Option<String>,
+) -> Result<(Option<String>, Option<String>), String> {
+    if capture_backend != CaptureBackendKind::WebDriver {
+        return Ok((None, None));
+    }
+
+    match (selenium_host, selenium_port) {
+        (Some(host), Some(port)) => Ok((Some(host), Some(port))),
+        (None, _) => Err("SELENIUM_HOST must be set for the webdriver capture backend".to_string()),
+        (_, None) => Err("SELENIUM_PORT must be set for the webdriver capture backend".to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -68,4 +148,34 @@ mod tests {
         assert_eq!(env_variables.port.len() > 0, true);
         assert_eq!(env_variables.db_url.len() > 0, true);
     }
+
+    #[test]
+    fn test_selenium_variables_only_required_for_webdriver() {
+        assert_eq!(
+            selenium_variables(CaptureBackendKind::Cdp, None, None),
+            Ok((None, None))
+        );
+        assert_eq!(
+            selenium_variables(
+                CaptureBackendKind::Fake,
+                Some("localhost".to_string()),
+                None
+            ),
+            Ok((None, None))
+        );
+        assert_eq!(
+            selenium_variables(
+                CaptureBackendKind::WebDriver,
+                Some("localhost".to_string()),
+                Some("4444".to_string())
+            ),
+            Ok((Some("localhost".to_string()), Some("4444".to_string())))
+        );
+        assert!(selenium_variables(
+            CaptureBackendKind::WebDriver,
+            Some("localhost".to_string()),
+            None
+        )
+        .is_err());
+    }
 }
@@ -0,0 +1,37 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use validator::ValidationError;
+
+lazy_static! {
+    // Names of viewports, devices, args variants and globals end up in file names and urls
+    static ref FILE_SAFE_NAME: Regex = Regex::new(r"^[A-Za-z0-9_-]+$").unwrap();
+}
+
+/// Whether `name` only has letters, digits, `_` and `-`
+pub fn is_file_safe_name(name: &str) -> bool {
+    FILE_SAFE_NAME.is_match(name)
+}
+
+pub fn validate_file_safe_name(name: &str) -> Result<(), ValidationError> {
+    if is_file_safe_name(name) {
+        Ok(())
+    } else {
+        Err(ValidationError::new(
+            "names may only contain letters, digits, _ and -",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_file_safe_name() {
+        assert!(validate_file_safe_name("iphone-14_landscape").is_ok());
+        assert!(validate_file_safe_name("").is_err());
+        assert!(validate_file_safe_name("dark mode").is_err());
+        assert!(validate_file_safe_name("a/b").is_err());
+        assert!(validate_file_safe_name("a@b").is_err());
+    }
+}
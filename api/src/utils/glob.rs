@@ -0,0 +1,24 @@
+use regex::Regex;
+
+/// Whether `value` matches `glob` as a whole, where `*` matches anything
+pub fn matches_glob(glob: &str, value: &str) -> bool {
+    let pattern = format!("^{}$", regex::escape(glob).replace(r"\*", ".*"));
+
+    Regex::new(&pattern)
+        .map(|regex| regex.is_match(value))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_glob() {
+        assert!(matches_glob("button--*", "button--primary"));
+        assert!(matches_glob("*/Button", "Components/Button"));
+        assert!(matches_glob("button--primary", "button--primary"));
+        assert!(!matches_glob("button--*", "icon-button--primary"));
+        assert!(!matches_glob("button.primary", "button-primary"));
+    }
+}
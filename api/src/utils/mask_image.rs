@@ -0,0 +1,155 @@
+use std::io::Cursor;
+
+use anyhow::Error;
+use image::{DynamicImage, GenericImage, ImageFormat, Rgba};
+
+use crate::models::{crop::CropRect, mask::MaskedRegion};
+
+const MASK_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// Blacks out the regions of an image that is `width` css pixels wide.
+/// The image may be larger than that when the device pixel ratio is above one.
+pub fn mask_regions(image: &mut DynamicImage, width: f64, regions: &[MaskedRegion]) {
+    let scale = if width > 0.0 {
+        image.width() as f64 / width
+    } else {
+        1.0
+    };
+
+    for region in regions {
+        let left = ((region.x * scale).floor().max(0.0) as u32).min(image.width());
+        let top = ((region.y * scale).floor().max(0.0) as u32).min(image.height());
+        let right = (((region.x + region.width) * scale).ceil().max(0.0) as u32).min(image.width());
+        let bottom =
+            (((region.y + region.height) * scale).ceil().max(0.0) as u32).min(image.height());
+
+        for x in left..right {
+            for y in top..bottom {
+                image.put_pixel(x, y, MASK_COLOR);
+            }
+        }
+    }
+}
+
+/// Blacks out the regions of an encoded screenshot
+pub fn mask_screenshot(
+    screenshot: &[u8],
+    width: f64,
+    regions: &[MaskedRegion],
+) -> Result<Vec<u8>, Error> {
+    let mut image = image::load_from_memory(screenshot)?;
+
+    mask_regions(&mut image, width, regions);
+
+    let mut bytes: Vec<u8> = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+
+    Ok(bytes)
+}
+
+/// Moves regions measured on the whole page into the coordinates of a capture
+/// cropped to `crop`, dropping those that end up outside of it
+pub fn regions_within(regions: Vec<MaskedRegion>, crop: &CropRect) -> Vec<MaskedRegion> {
+    regions
+        .into_iter()
+        .filter_map(|region| {
+            let left = region.x.max(crop.x);
+            let top = region.y.max(crop.y);
+            let right = (region.x + region.width).min(crop.x + crop.width);
+            let bottom = (region.y + region.height).min(crop.y + crop.height);
+
+            if right <= left || bottom <= top {
+                return None;
+            }
+
+            Some(MaskedRegion {
+                x: left - crop.x,
+                y: top - crop.y,
+                width: right - left,
+                height: bottom - top,
+                ..region
+            })
+        })
+        .collect()
+}
+
+/// Regions masked in either of two captures, without duplicates
+pub fn merge_regions(regions_1: &[MaskedRegion], regions_2: &[MaskedRegion]) -> Vec<MaskedRegion> {
+    let mut regions = regions_1.to_vec();
+
+    for region in regions_2 {
+        if !regions.contains(region) {
+            regions.push(region.clone());
+        }
+    }
+
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    fn region(x: f64, y: f64, width: f64, height: f64) -> MaskedRegion {
+        MaskedRegion {
+            selector: ".date".to_string(),
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_mask_regions() {
+        let mut image = DynamicImage::new_rgba8(20, 10);
+
+        mask_regions(&mut image, 10.0, &[region(1.0, 1.0, 2.0, 2.0)]);
+
+        assert_eq!(image.get_pixel(2, 2), MASK_COLOR);
+        assert_eq!(image.get_pixel(5, 5), MASK_COLOR);
+        assert_ne!(image.get_pixel(6, 6), MASK_COLOR);
+        assert_ne!(image.get_pixel(1, 1), MASK_COLOR);
+    }
+
+    #[test]
+    fn test_mask_regions_outside_of_image() {
+        let mut image = DynamicImage::new_rgba8(10, 10);
+
+        mask_regions(&mut image, 10.0, &[region(8.0, -5.0, 20.0, 7.0)]);
+
+        assert_eq!(image.get_pixel(9, 1), MASK_COLOR);
+        assert_ne!(image.get_pixel(9, 2), MASK_COLOR);
+    }
+
+    #[test]
+    fn test_regions_within() {
+        let crop = CropRect {
+            x: 10.0,
+            y: 10.0,
+            width: 50.0,
+            height: 50.0,
+        };
+
+        let regions = regions_within(
+            vec![
+                region(0.0, 20.0, 20.0, 10.0),
+                region(100.0, 100.0, 5.0, 5.0),
+            ],
+            &crop,
+        );
+
+        assert_eq!(regions, vec![region(0.0, 10.0, 10.0, 10.0)]);
+    }
+
+    #[test]
+    fn test_merge_regions() {
+        let regions = merge_regions(
+            &[region(0.0, 0.0, 5.0, 5.0)],
+            &[region(0.0, 0.0, 5.0, 5.0), region(10.0, 0.0, 5.0, 5.0)],
+        );
+
+        assert_eq!(regions.len(), 2);
+    }
+}
@@ -1,6 +1,17 @@
+pub mod batch_cancellation;
+pub mod batch_events;
+pub mod capture_backend;
 pub mod capture_screenshots;
 pub mod compare_images;
+pub mod crop_image;
 pub mod date_format;
 pub mod env_variables;
+pub mod file_safe_name;
+pub mod glob;
+pub mod mask_image;
+pub mod readiness;
 pub mod save_images;
+pub mod session_pool;
+pub mod stabilization;
+pub mod stitch_image;
 pub mod story_book;
@@ -0,0 +1,360 @@
+use std::time::Duration;
+
+use anyhow::Error;
+
+use crate::models::{capture_options::CaptureOptions, readiness_strategy::ReadinessStrategy};
+
+use super::{capture_backend::CapturePage, env_variables::EnvVariables};
+
+const INTERVAL: Duration = Duration::from_millis(100);
+/// How long no new resource may be fetched for the network to count as idle
+const NETWORK_IDLE: Duration = Duration::from_millis(500);
+
+const RENDER_COMPLETE_SCRIPT: &str = r"
+    var preview = window.__STORYBOOK_PREVIEW__;
+    if (preview && preview.currentRender) {
+        return ['played', 'completed'].indexOf(preview.currentRender.phase) !== -1;
+    }
+    var root = document.querySelector('#storybook-root, #root');
+    return document.readyState === 'complete' && !!root && root.childElementCount > 0;
+";
+
+/// Whether the docs page finished rendering its blocks and loading its fonts. Storybook
+/// marks the body while it prepares docs and renders them into their own root
+const DOCS_RENDERED_SCRIPT: &str = r"
+    var root = document.querySelector('#storybook-docs, #docs-root');
+    return document.readyState === 'complete'
+        && !document.body.classList.contains('sb-show-preparing-docs')
+        && !!root && root.childElementCount > 0
+        && document.fonts.status === 'loaded';
+";
+
+/// Whether the play function of the story is still `playing`, is done or threw an `error`.
+/// Errors are caught through the preview channel, as the render only knows that it failed
+const PLAY_FUNCTION_SCRIPT: &str = r"
+    var channel = window.__STORYBOOK_ADDONS_CHANNEL__;
+    if (channel && !window.__snapShotPlayListener) {
+        window.__snapShotPlayListener = true;
+        channel.on('playFunctionThrewException', function (error) {
+            window.__snapShotPlayError = (error && error.message) || String(error);
+        });
+    }
+    if (window.__snapShotPlayError) {
+        return { error: window.__snapShotPlayError };
+    }
+    var preview = window.__STORYBOOK_PREVIEW__;
+    var render = preview && preview.currentRender;
+    if (!render) {
+        return 'done';
+    }
+    if (!render.story) {
+        return 'playing';
+    }
+    if (!render.story.playFunction) {
+        return 'done';
+    }
+    if (render.phase === 'errored') {
+        return { error: 'the story errored while playing' };
+    }
+    return ['played', 'completed'].indexOf(render.phase) !== -1 ? 'done' : 'playing';
+";
+
+/// Whether an element matches the css selector or, with the second argument set, the xpath
+const ELEMENT_EXISTS_SCRIPT: &str = r"
+    if (arguments[1]) {
+        return !!document.evaluate(arguments[0], document, null, XPathResult.FIRST_ORDERED_NODE_TYPE, null).singleNodeValue;
+    }
+    return !!document.querySelector(arguments[0]);
+";
+
+const FONTS_LOADED_SCRIPT: &str = r"
+    return document.readyState === 'complete' && document.fonts.status === 'loaded';
+";
+
+const RESOURCE_COUNT_SCRIPT: &str = r"
+    if (document.readyState !== 'complete') {
+        return -1;
+    }
+    return performance.getEntriesByType('resource').length;
+";
+
+/// Readiness strategy of a batch, with the server defaults filled in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Readiness {
+    pub strategy: ReadinessStrategy,
+    pub timeout: Duration,
+}
+
+impl Readiness {
+    pub fn new(options: &CaptureOptions, env_variables: &EnvVariables) -> Self {
+        Self {
+            strategy: options
+                .readiness
+                .clone()
+                .unwrap_or_else(|| env_variables.readiness_strategy.clone()),
+            timeout: Duration::from_millis(
+                options
+                    .readiness_timeout_ms
+                    .unwrap_or(env_variables.readiness_timeout_ms),
+            ),
+        }
+    }
+
+    /// Time the strategy may take. A fixed delay would use up the timeout by itself, so the
+    /// timeout starts once it is over
+    fn deadline(&self) -> Duration {
+        match self.strategy {
+            ReadinessStrategy::Delay { ms } => self.timeout + Duration::from_millis(ms),
+            _ => self.timeout,
+        }
+    }
+}
+
+/// Waits until the story loaded in the page is ready to be captured
+pub async fn wait_until_ready(page: &dyn CapturePage, readiness: &Readiness) -> Result<(), Error> {
+    tokio::time::timeout(readiness.deadline(), async {
+        wait_for_strategy(page, &readiness.strategy).await?;
+        // Stories with a play function would otherwise be captured mid-interaction
+        wait_for_play_function(page).await
+    })
+    .await
+    .map_err(|_| {
+        Error::msg(format!(
+            "Story was not ready after {}ms waiting for {}",
+            readiness.deadline().as_millis(),
+            readiness.strategy
+        ))
+    })?
+}
+
+/// Waits until the docs page loaded in the page is ready to be captured. Docs have no play
+/// function and do not render into the story root, so the render check is replaced by one
+/// for docs, while the other strategies still apply
+pub async fn wait_until_docs_ready(
+    page: &dyn CapturePage,
+    readiness: &Readiness,
+) -> Result<(), Error> {
+    tokio::time::timeout(readiness.deadline(), async {
+        wait_for_script(page, DOCS_RENDERED_SCRIPT, vec![]).await?;
+
+        match readiness.strategy {
+            ReadinessStrategy::RenderComplete => Ok(()),
+            _ => wait_for_strategy(page, &readiness.strategy).await,
+        }
+    })
+    .await
+    .map_err(|_| {
+        Error::msg(format!(
+            "Docs were not ready after {}ms waiting for {}",
+            readiness.deadline().as_millis(),
+            readiness.strategy
+        ))
+    })?
+}
+
+async fn wait_for_strategy(
+    page: &dyn CapturePage,
+    strategy: &ReadinessStrategy,
+) -> Result<(), Error> {
+    match strategy {
+        ReadinessStrategy::Css { selector } => wait_for_element(page, selector, false).await,
+        ReadinessStrategy::XPath { selector } => wait_for_element(page, selector, true).await,
+        ReadinessStrategy::RenderComplete => {
+            wait_for_script(page, RENDER_COMPLETE_SCRIPT, vec![]).await
+        }
+        ReadinessStrategy::FontsLoaded => wait_for_script(page, FONTS_LOADED_SCRIPT, vec![]).await,
+        ReadinessStrategy::NetworkIdle => wait_for_network_idle(page).await,
+        ReadinessStrategy::Delay { ms } => {
+            tokio::time::sleep(Duration::from_millis(*ms)).await;
+            Ok(())
+        }
+    }
+}
+
+async fn wait_for_element(
+    page: &dyn CapturePage,
+    selector: &str,
+    is_xpath: bool,
+) -> Result<(), Error> {
+    wait_for_script(
+        page,
+        ELEMENT_EXISTS_SCRIPT,
+        vec![
+            serde_json::Value::String(selector.to_string()),
+            serde_json::Value::Bool(is_xpath),
+        ],
+    )
+    .await
+}
+
+async fn wait_for_script(
+    page: &dyn CapturePage,
+    script: &str,
+    args: Vec<serde_json::Value>,
+) -> Result<(), Error> {
+    while !page
+        .execute(script, args.clone())
+        .await?
+        .as_bool()
+        .unwrap_or(false)
+    {
+        tokio::time::sleep(INTERVAL).await;
+    }
+
+    Ok(())
+}
+
+async fn wait_for_play_function(page: &dyn CapturePage) -> Result<(), Error> {
+    loop {
+        let state = page.execute(PLAY_FUNCTION_SCRIPT, vec![]).await?;
+
+        if let Some(error) = state.get("error") {
+            return Err(Error::msg(format!(
+                "Play function threw {}",
+                error.as_str().unwrap_or("an error")
+            )));
+        }
+
+        if state.as_str() != Some("playing") {
+            return Ok(());
+        }
+
+        tokio::time::sleep(INTERVAL).await;
+    }
+}
+
+/// Only finished requests show up as resource entries, so the network counts
+/// as idle once their number stops changing for a while
+async fn wait_for_network_idle(page: &dyn CapturePage) -> Result<(), Error> {
+    let mut last_count = -1;
+    let mut idle_for = Duration::ZERO;
+
+    while idle_for < NETWORK_IDLE {
+        let count = page
+            .execute(RESOURCE_COUNT_SCRIPT, vec![])
+            .await?
+            .as_i64()
+            .unwrap_or(-1);
+
+        if count >= 0 && count == last_count {
+            idle_for += INTERVAL;
+        } else {
+            idle_for = Duration::ZERO;
+        }
+
+        last_count = count;
+        tokio::time::sleep(INTERVAL).await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::browser::Browser,
+        utils::capture_backend::{fake::FakeBackend, CaptureBackend},
+    };
+
+    fn render_complete(timeout_ms: u64) -> Readiness {
+        Readiness {
+            strategy: ReadinessStrategy::RenderComplete,
+            timeout: Duration::from_millis(timeout_ms),
+        }
+    }
+
+    #[test]
+    fn test_readiness_prefers_capture_options() {
+        let env_variables = EnvVariables::new();
+
+        let readiness = Readiness::new(&CaptureOptions::default(), &env_variables);
+
+        assert_eq!(readiness.strategy, env_variables.readiness_strategy);
+        assert_eq!(
+            readiness.timeout,
+            Duration::from_millis(env_variables.readiness_timeout_ms)
+        );
+
+        let readiness = Readiness::new(
+            &CaptureOptions {
+                readiness: Some(ReadinessStrategy::FontsLoaded),
+                readiness_timeout_ms: Some(100),
+                ..Default::default()
+            },
+            &env_variables,
+        );
+
+        assert_eq!(readiness.strategy, ReadinessStrategy::FontsLoaded);
+        assert_eq!(readiness.timeout, Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_ready_waits_for_play_function() {
+        let playing = FakeBackend::default().with_script_result("playFunction", "playing".into());
+        let page = playing.open_page(Browser::Chrome, None).await.unwrap();
+
+        let err = wait_until_ready(page.as_ref(), &render_complete(300))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("was not ready"));
+
+        let played = FakeBackend::default().with_script_result("playFunction", "done".into());
+        let page = played.open_page(Browser::Chrome, None).await.unwrap();
+
+        assert!(wait_until_ready(page.as_ref(), &render_complete(300))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_ready_waits_for_delay_longer_than_timeout() {
+        let readiness = Readiness {
+            strategy: ReadinessStrategy::Delay { ms: 400 },
+            timeout: Duration::from_millis(100),
+        };
+        let backend = FakeBackend::default();
+        let page = backend.open_page(Browser::Chrome, None).await.unwrap();
+
+        assert!(wait_until_ready(page.as_ref(), &readiness).await.is_ok());
+        assert!(wait_until_docs_ready(page.as_ref(), &readiness)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_ready_fails_when_play_function_throws() {
+        let backend = FakeBackend::default().with_script_result(
+            "playFunction",
+            serde_json::json!({ "error": "Unable to find button" }),
+        );
+        let page = backend.open_page(Browser::Chrome, None).await.unwrap();
+
+        let err = wait_until_ready(page.as_ref(), &render_complete(300))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "Play function threw Unable to find button");
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_docs_ready() {
+        let preparing = FakeBackend::default()
+            .with_script_result("sb-show-preparing-docs", false.into())
+            .with_script_result("playFunction", "playing".into());
+        let page = preparing.open_page(Browser::Chrome, None).await.unwrap();
+
+        let err = wait_until_docs_ready(page.as_ref(), &render_complete(300))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Docs were not ready"));
+
+        // Docs never finish playing, as they have no play function to wait for
+        let rendered = FakeBackend::default().with_script_result("playFunction", "playing".into());
+        let page = rendered.open_page(Browser::Chrome, None).await.unwrap();
+
+        assert!(wait_until_docs_ready(page.as_ref(), &render_complete(300))
+            .await
+            .is_ok());
+    }
+}
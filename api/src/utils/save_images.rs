@@ -2,19 +2,14 @@ use std::{fs, path::Path};
 
 use anyhow::Error;
 
-use super::env_variables;
-
 pub fn safe_save_image(
     raw_image: Vec<u8>,
+    assets_folder: &str,
     folder: &str,
     image_name: &str,
 ) -> Result<String, Error> {
-    let env_variables = env_variables::EnvVariables::new();
-    let file_name = format!(
-        "{}/{}/{}.png",
-        env_variables.assets_folder, folder, image_name
-    );
-    let path_str = format!("{}/{}", env_variables.assets_folder, folder);
+    let file_name = format!("{}/{}/{}.png", assets_folder, folder, image_name);
+    let path_str = format!("{}/{}", assets_folder, folder);
     let path = Path::new(path_str.as_str());
 
     if !Path::exists(path) {
@@ -48,7 +43,7 @@ pub fn safe_copy_image(from: &str, to: &str) -> Result<String, Error> {
 //         let folder = "test";
 //         let image_name = "test_image";
 
-//         let result = safe_save_image(image, folder, image_name);
+//         let result = safe_save_image(image, "assets", folder, image_name);
 
 //         assert_eq!(result.is_ok(), true);
 
@@ -0,0 +1,266 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use anyhow::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::models::{browser::Browser, device::Device};
+
+use super::{
+    capture_backend::{self, CaptureBackend, CapturePage},
+    env_variables::EnvVariables,
+};
+
+/// Browser sessions shared by every batch. At most `max_sessions` are open at
+/// once, idle ones are reused and sessions are replaced after a number of navigations
+/// so that a long running browser does not slow down or leak memory.
+#[derive(Clone)]
+pub struct SessionPool {
+    inner: Arc<SessionPoolInner>,
+}
+
+struct SessionPoolInner {
+    backend: Arc<dyn CaptureBackend>,
+    max_navigations: u32,
+    permits: Arc<Semaphore>,
+    idle: Mutex<Vec<IdleSession>>,
+    closed: AtomicBool,
+}
+
+struct IdleSession {
+    browser: Browser,
+    device: Option<Device>,
+    page: Box<dyn CapturePage>,
+    navigations: u32,
+}
+
+/// A session borrowed from the pool, given back when dropped
+pub struct Session {
+    browser: Browser,
+    device: Option<Device>,
+    page: Option<Box<dyn CapturePage>>,
+    navigations: u32,
+    /// Closed instead of given back, as its page may be in any state
+    poisoned: bool,
+    pool: SessionPool,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl SessionPool {
+    pub fn new(
+        backend: Arc<dyn CaptureBackend>,
+        max_sessions: usize,
+        max_navigations: u32,
+    ) -> Self {
+        Self {
+            inner: Arc::new(SessionPoolInner {
+                backend,
+                max_navigations,
+                permits: Arc::new(Semaphore::new(max_sessions)),
+                idle: Mutex::new(vec![]),
+                closed: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    pub fn from_env(env_variables: &EnvVariables) -> Self {
+        Self::new(
+            capture_backend::from_env(env_variables),
+            env_variables.selenium_max_sessions,
+            env_variables.selenium_session_max_navigations,
+        )
+    }
+
+    /// Waits for a free slot and hands out an idle session of the browser emulating
+    /// the same device, or opens a new one
+    pub async fn acquire(
+        &self,
+        browser: Browser,
+        device: Option<&Device>,
+    ) -> Result<Session, Error> {
+        let permit = self
+            .inner
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| Error::msg("Session pool is closed"))?;
+
+        loop {
+            let idle = {
+                let mut idle = self.inner.idle.lock().unwrap();
+                idle.iter()
+                    .rposition(|session| {
+                        session.browser == browser && session.device.as_ref() == device
+                    })
+                    .map(|index| idle.remove(index))
+            };
+
+            let idle = match idle {
+                Some(idle) => idle,
+                None => break,
+            };
+
+            if idle.page.is_healthy().await {
+                return Ok(Session {
+                    browser,
+                    device: idle.device,
+                    page: Some(idle.page),
+                    navigations: idle.navigations,
+                    poisoned: false,
+                    pool: self.clone(),
+                    _permit: permit,
+                });
+            }
+
+            tracing::warn!("Dropping unhealthy browser session");
+            idle.page.close().await;
+        }
+
+        // Idle sessions of other browsers or devices still count towards the limit, so make room for the new one
+        let other_session = {
+            let mut idle = self.inner.idle.lock().unwrap();
+            (!idle.is_empty()).then(|| idle.remove(0))
+        };
+
+        if let Some(idle) = other_session {
+            idle.page.close().await;
+        }
+
+        let page = self.inner.backend.open_page(browser, device).await?;
+
+        Ok(Session {
+            browser,
+            device: device.cloned(),
+            page: Some(page),
+            navigations: 0,
+            poisoned: false,
+            pool: self.clone(),
+            _permit: permit,
+        })
+    }
+
+    /// Stops handing out sessions and ends the idle ones. Sessions still in
+    /// use are ended when they are given back.
+    pub async fn close(&self) {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        self.inner.permits.close();
+
+        let idle: Vec<IdleSession> = self.inner.idle.lock().unwrap().drain(..).collect();
+
+        for session in idle {
+            session.page.close().await;
+        }
+
+        self.inner.backend.close().await;
+    }
+
+    fn release(
+        &self,
+        browser: Browser,
+        device: Option<Device>,
+        page: Box<dyn CapturePage>,
+        navigations: u32,
+    ) {
+        if self.inner.closed.load(Ordering::SeqCst) || navigations >= self.inner.max_navigations {
+            tokio::spawn(page.close());
+            return;
+        }
+
+        self.inner.idle.lock().unwrap().push(IdleSession {
+            browser,
+            device,
+            page,
+            navigations,
+        });
+    }
+}
+
+impl Session {
+    pub fn page(&self) -> &dyn CapturePage {
+        self.page.as_deref().unwrap()
+    }
+
+    /// Counts towards the navigations after which the session is replaced
+    pub fn record_navigation(&mut self) {
+        self.navigations += 1;
+    }
+
+    /// Closes the session when it is dropped rather than giving it back, e.g. after a
+    /// capture was cut off halfway and left the page loading or interacted with
+    pub fn poison(&mut self) {
+        self.poisoned = true;
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(page) = self.page.take() {
+            if self.poisoned {
+                tokio::spawn(page.close());
+                return;
+            }
+
+            self.pool
+                .release(self.browser, self.device.take(), page, self.navigations);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils::capture_backend::fake::FakeBackend;
+
+    #[tokio::test]
+    async fn test_closed_session_pool_refuses_sessions() {
+        let session_pool = SessionPool::new(Arc::new(FakeBackend::default()), 1, 1);
+
+        session_pool.close().await;
+
+        assert!(session_pool.acquire(Browser::Chrome, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_session_pool_reuses_sessions_of_the_same_device() {
+        let session_pool = SessionPool::new(Arc::new(FakeBackend::default()), 1, 10);
+        let ipad = Device::preset("ipad").unwrap();
+
+        let mut session = session_pool
+            .acquire(Browser::Chrome, Some(&ipad))
+            .await
+            .unwrap();
+        session.record_navigation();
+        drop(session);
+
+        let session = session_pool
+            .acquire(Browser::Chrome, Some(&ipad))
+            .await
+            .unwrap();
+        assert_eq!(session.navigations, 1);
+        drop(session);
+
+        // The idle ipad session makes room for a session without a device
+        let session = session_pool.acquire(Browser::Chrome, None).await.unwrap();
+        assert_eq!(session.navigations, 0);
+        assert!(session_pool.inner.idle.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_session_pool_closes_poisoned_sessions() {
+        let session_pool = SessionPool::new(Arc::new(FakeBackend::default()), 1, 10);
+
+        let mut session = session_pool.acquire(Browser::Chrome, None).await.unwrap();
+        session.record_navigation();
+        session.poison();
+        drop(session);
+
+        assert!(session_pool.inner.idle.lock().unwrap().is_empty());
+
+        let session = session_pool.acquire(Browser::Chrome, None).await.unwrap();
+        assert_eq!(session.navigations, 0);
+    }
+}
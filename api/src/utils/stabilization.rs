@@ -0,0 +1,116 @@
+use anyhow::Error;
+
+use crate::models::stabilization::Stabilization;
+
+use super::capture_backend::CapturePage;
+
+/// 2024-01-01T00:00:00Z, what `Date` returns while time is frozen
+const FROZEN_TIME_MS: u64 = 1_704_067_200_000;
+const RANDOM_SEED: u32 = 1;
+
+const STABILIZATION_SCRIPT: &str = r"
+    var options = arguments[0];
+    var css = '';
+
+    if (options.disable_animations) {
+        css += '*, *::before, *::after { animation: none !important; transition: none !important; scroll-behavior: auto !important; }';
+    }
+    if (options.hide_caret) {
+        css += '* { caret-color: transparent !important; }';
+    }
+    if (css) {
+        var style = document.createElement('style');
+        style.appendChild(document.createTextNode(css));
+        document.head.appendChild(style);
+    }
+
+    if (options.pause_media) {
+        document.querySelectorAll('video, audio').forEach(function (media) {
+            media.pause();
+            media.currentTime = 0;
+        });
+        document.querySelectorAll('img').forEach(function (img) {
+            if (!/\.gif($|\?)/i.test(img.currentSrc || img.src) || !img.complete || !img.naturalWidth) {
+                return;
+            }
+            try {
+                var canvas = document.createElement('canvas');
+                canvas.width = img.naturalWidth;
+                canvas.height = img.naturalHeight;
+                canvas.getContext('2d').drawImage(img, 0, 0);
+                img.src = canvas.toDataURL();
+            } catch (e) {
+                // Cross origin images can't be read back from a canvas
+            }
+        });
+    }
+
+    if (options.freeze_time) {
+        var frozenTime = options.frozen_time_ms;
+        var RealDate = window.Date;
+        var FrozenDate = function () {
+            var args = Array.prototype.slice.call(arguments);
+            if (!(this instanceof FrozenDate)) {
+                return new RealDate(frozenTime).toString();
+            }
+            if (args.length === 0) {
+                return new RealDate(frozenTime);
+            }
+            return new (Function.prototype.bind.apply(RealDate, [null].concat(args)))();
+        };
+        FrozenDate.prototype = RealDate.prototype;
+        FrozenDate.now = function () { return frozenTime; };
+        FrozenDate.parse = RealDate.parse;
+        FrozenDate.UTC = RealDate.UTC;
+        window.Date = FrozenDate;
+    }
+
+    if (options.seed_random) {
+        var seed = options.random_seed;
+        Math.random = function () {
+            seed = (seed + 0x6D2B79F5) | 0;
+            var t = Math.imul(seed ^ (seed >>> 15), 1 | seed);
+            t = (t + Math.imul(t ^ (t >>> 7), 61 | t)) ^ t;
+            return ((t ^ (t >>> 14)) >>> 0) / 4294967296;
+        };
+    }
+
+    // The story already rendered with the real clock and random numbers, so render it again
+    var channel = window.__STORYBOOK_ADDONS_CHANNEL__;
+    if ((options.freeze_time || options.seed_random) && channel) {
+        channel.emit('forceReRender');
+        return true;
+    }
+    return false;
+";
+
+/// Resolves once the browser has painted twice, by which point a re-render has started
+const NEXT_FRAME_SCRIPT: &str = r"
+    var done = arguments[arguments.length - 1];
+    requestAnimationFrame(function () {
+        requestAnimationFrame(function () { done(true); });
+    });
+";
+
+/// Injects the stabilization script into the story loaded in the page.
+/// Returns `true` when the story was rendered again and has to be waited for.
+pub async fn stabilize(
+    page: &dyn CapturePage,
+    stabilization: &Stabilization,
+) -> Result<bool, Error> {
+    let mut options = serde_json::to_value(stabilization)?;
+    options["frozen_time_ms"] = serde_json::json!(FROZEN_TIME_MS);
+    options["random_seed"] = serde_json::json!(RANDOM_SEED);
+
+    let rendered_again = page
+        .execute(STABILIZATION_SCRIPT, vec![options])
+        .await?
+        .as_bool()
+        .unwrap_or(false);
+
+    if rendered_again {
+        page.execute_async(NEXT_FRAME_SCRIPT, vec![]).await?;
+    }
+
+    Ok(rendered_again)
+}
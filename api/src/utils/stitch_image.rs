@@ -0,0 +1,99 @@
+use std::io::Cursor;
+
+use anyhow::Error;
+use image::{imageops, ImageFormat, RgbaImage};
+
+/// Screenshot of the viewport, taken while the page was scrolled down by `scroll_y` css pixels
+pub struct Tile {
+    pub image: Vec<u8>,
+    pub scroll_y: f64,
+}
+
+/// Stitches screenshots of the viewport into one image of a page that is `width` by
+/// `height` css pixels. Where tiles overlap, such as at the bottom of the page where
+/// the browser stops scrolling, later tiles win. The tiles may be larger than the
+/// page when the device pixel ratio is above one, so it is scaled accordingly.
+pub fn stitch_tiles(tiles: &[Tile], width: f64, height: f64) -> Result<Vec<u8>, Error> {
+    let first = tiles
+        .first()
+        .ok_or_else(|| Error::msg("No screenshots to stitch"))?;
+    let first = image::load_from_memory(&first.image)?;
+
+    let scale = if width > 0.0 {
+        first.width() as f64 / width
+    } else {
+        1.0
+    };
+
+    let mut page = RgbaImage::new(first.width(), ((height * scale).round() as u32).max(1));
+
+    for tile in tiles {
+        let image = image::load_from_memory(&tile.image)?.to_rgba8();
+        imageops::replace(&mut page, &image, 0, (tile.scroll_y * scale).round() as i64);
+    }
+
+    let mut bytes: Vec<u8> = Vec::new();
+    page.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    fn tile(width: u32, height: u32, color: [u8; 4], scroll_y: f64) -> Tile {
+        let mut bytes: Vec<u8> = Vec::new();
+        RgbaImage::from_pixel(width, height, Rgba(color))
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+
+        Tile {
+            image: bytes,
+            scroll_y,
+        }
+    }
+
+    #[test]
+    fn test_stitch_tiles() {
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+
+        // The browser stopped scrolling at 15, so the last tile overlaps the first
+        let stitched = stitch_tiles(
+            &[tile(20, 20, red, 0.0), tile(20, 20, blue, 15.0)],
+            20.0,
+            35.0,
+        )
+        .unwrap();
+        let stitched = image::load_from_memory(&stitched).unwrap().to_rgba8();
+
+        assert_eq!(stitched.dimensions(), (20, 35));
+        assert_eq!(*stitched.get_pixel(0, 14), Rgba(red));
+        assert_eq!(*stitched.get_pixel(0, 15), Rgba(blue));
+        assert_eq!(*stitched.get_pixel(0, 34), Rgba(blue));
+    }
+
+    #[test]
+    fn test_stitch_tiles_scales_to_pixel_ratio() {
+        let stitched = stitch_tiles(
+            &[
+                tile(40, 20, [255, 0, 0, 255], 0.0),
+                tile(40, 20, [0, 0, 255, 255], 10.0),
+            ],
+            20.0,
+            15.0,
+        )
+        .unwrap();
+        let stitched = image::load_from_memory(&stitched).unwrap();
+
+        assert_eq!((stitched.width(), stitched.height()), (40, 30));
+    }
+
+    #[test]
+    fn test_stitch_no_tiles() {
+        assert!(stitch_tiles(&[], 20.0, 20.0).is_err());
+    }
+}
@@ -1,14 +1,33 @@
 use std::collections::HashMap;
 
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
 use anyhow::Error;
+use url::Url;
 
-use crate::models::snapshot::SnapShotType;
+use crate::models::{
+    browser::Browser,
+    capture_options::CaptureOptions,
+    globals::{self, Globals},
+    snapshot::SnapShotType,
+    story::{Story, StoryKind},
+    story_filter::StoryFilter,
+};
 
 use super::capture_screenshots::ScreenShotParams;
 
+/// Files Storybook publishes its index in, newest first
+const INDEX_FILES: [&str; 2] = ["index.json", "stories.json"];
+
+lazy_static! {
+    /// Start of the refs the manager page of a Storybook is given, e.g. `window['REFS'] = {`
+    static ref REFS_ASSIGNMENT: Regex =
+        Regex::new(r#"window(?:\[['"]REFS['"]\]|\.REFS)\s*=\s*"#).unwrap();
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde_with::serde_as]
 pub struct StoryBookConfig {
@@ -23,70 +42,1007 @@ pub struct StoryBookConfigEntry {
     pub name: String,
     pub title: String,
     pub r#type: String,
+    /// Only given from Storybook 6.4 on
+    #[serde(rename = "importPath")]
+    pub import_path: Option<String>,
+    /// Only given from Storybook 7 on
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Ref the entry is composed from, which is not part of the index
+    #[serde(skip)]
+    pub story_book_ref: Option<StoryBookRef>,
+}
+
+impl StoryBookConfigEntry {
+    /// Id of the entry in the host, which prefixes the stories of refs with the id of their
+    /// ref as Storybook does, so that stories of the same name in different refs differ
+    fn composed_id(&self) -> String {
+        match &self.story_book_ref {
+            Some(story_book_ref) => format!("{}_{}", story_book_ref.id, self.id),
+            None => self.id.clone(),
+        }
+    }
+
+    /// Title of the entry in the sidebar of the host, which lists refs under their own title
+    fn composed_title(&self) -> String {
+        match &self.story_book_ref {
+            Some(story_book_ref) => format!("{}/{}", story_book_ref.title, self.title),
+            None => self.title.clone(),
+        }
+    }
+}
+
+/// Storybook composed into the host through `refs`, whose stories are only in its own index
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoryBookRef {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+}
+
+/// A ref as the manager page lists it, keyed by its id
+#[derive(Deserialize, Debug)]
+struct StoryBookRefConfig {
+    title: Option<String>,
+    url: String,
+}
+
+/// Either file Storybook publishes its stories in, before its version is checked
+#[derive(Deserialize, Debug)]
+struct StoryBookIndex {
+    v: i64,
+    /// Stories and docs in `index.json`, version 4 in Storybook 7 and 5 from Storybook 8 on
+    entries: Option<HashMap<String, StoryBookConfigEntry>>,
+    /// Stories in `stories.json` of Storybook 6, version 3
+    stories: Option<HashMap<String, StoriesJsonEntry>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct StoriesJsonEntry {
+    id: String,
+    name: String,
+    /// Only given from Storybook 6.4 on, which still sets `kind` to the same value
+    title: Option<String>,
+    kind: Option<String>,
+    #[serde(rename = "importPath")]
+    import_path: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    parameters: StoriesJsonParameters,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct StoriesJsonParameters {
+    /// Set on the pages of MDX docs, which have no story to capture
+    #[serde(rename = "docsOnly", default)]
+    docs_only: bool,
+}
+
+impl StoriesJsonEntry {
+    fn into_entry(self) -> StoryBookConfigEntry {
+        StoryBookConfigEntry {
+            id: self.id,
+            name: self.name,
+            title: self.title.or(self.kind).unwrap_or_default(),
+            r#type: if self.parameters.docs_only {
+                "docs".to_string()
+            } else {
+                "story".to_string()
+            },
+            import_path: self.import_path,
+            tags: self.tags,
+            story_book_ref: None,
+        }
+    }
 }
 
 pub async fn get_screenshot_params_by_url(
     url: &str,
     image_type: &SnapShotType,
+    options: &CaptureOptions,
 ) -> Result<Vec<ScreenShotParams>, Error> {
-    let story_book_config = get_story_book_config(url).await.map_err(|err| {
+    let mut story_book_config = get_story_book_config(url).await.map_err(|err| {
         tracing::error!("Failed to get story book config for url {}\n{}", url, err);
-        anyhow::Error::msg(format!("Failed to find story book config at: {}", url))
+        anyhow::Error::msg(format!(
+            "Failed to find story book config at: {}\n{}",
+            url, err
+        ))
     })?;
 
-    let config_filtered = story_book_config
-        .entries
-        .into_iter()
-        .filter(|entry| entry.1.r#type == "story")
-        .collect();
+    let story_book_refs = get_story_book_refs(url).await;
+    add_story_book_refs(&mut story_book_config, story_book_refs).await;
 
     Ok(get_screen_shot_params_from_config(
-        StoryBookConfig {
-            v: story_book_config.v,
-            entries: config_filtered,
-        },
+        select_stories(story_book_config, &options.filter, options.docs),
         url,
         image_type,
+        options,
     ))
 }
 
+/// Leaves out the stories the filter does not match, and docs unless they are captured as
+/// well, before anything is captured
+fn select_stories(config: StoryBookConfig, filter: &StoryFilter, docs: bool) -> StoryBookConfig {
+    let entries = config
+        .entries
+        .into_iter()
+        .filter(|entry| entry.1.r#type == "story" || (docs && entry.1.r#type == "docs"))
+        .filter(|entry| {
+            filter.matches(
+                &entry.1.composed_id(),
+                &entry.1.composed_title(),
+                &entry.1.tags,
+            )
+        })
+        .collect();
+
+    StoryBookConfig {
+        v: config.v,
+        entries,
+    }
+}
+
+/// Loads the index of the first file Storybook published, trying `index.json` of
+/// Storybook 7 and later before `stories.json` of Storybook 6
 async fn get_story_book_config(url: &str) -> Result<StoryBookConfig, Error> {
-    let response: reqwest::Response = reqwest::get(format!("{}/index.json", url)).await?;
+    let mut attempts: Vec<String> = vec![];
+
+    for file in INDEX_FILES {
+        match get_story_book_index(url, file).await {
+            Ok(config) => return Ok(config),
+            Err(err) => attempts.push(format!("{} ({})", file, err)),
+        }
+    }
+
+    Err(Error::msg(format!(
+        "No supported Storybook index found, tried {}",
+        attempts.join(", ")
+    )))
+}
+
+async fn get_story_book_index(url: &str, file: &str) -> Result<StoryBookConfig, Error> {
+    let response: reqwest::Response = reqwest::get(format!("{}/{}", url, file))
+        .await?
+        .error_for_status()?;
     let body = response.text().await?;
 
-    let config: StoryBookConfig = serde_json::from_str(body.as_str())?;
+    parse_story_book_index(body.as_str())
+}
+
+/// Refs the host composes, as its manager page lists them. Storybooks published without a
+/// manager page compose none, as do those whose refs can not be read
+async fn get_story_book_refs(url: &str) -> Vec<StoryBookRef> {
+    let html = match get_manager_page(url).await {
+        Ok(html) => html,
+        Err(err) => {
+            tracing::warn!("Unable to load the manager page of {}\n{}", url, err);
+            return vec![];
+        }
+    };
+
+    parse_story_book_refs(&html, url).unwrap_or_else(|err| {
+        tracing::warn!("Unable to read the refs of {}\n{}", url, err);
+        vec![]
+    })
+}
+
+/// Adds the entries of every ref whose index loads. A ref that is down or whose index can
+/// not be read only leaves out its own stories, the host and the other refs are captured
+async fn add_story_book_refs(config: &mut StoryBookConfig, story_book_refs: Vec<StoryBookRef>) {
+    for story_book_ref in story_book_refs {
+        match get_story_book_config(&story_book_ref.url).await {
+            Ok(ref_config) => add_ref_entries(config, &story_book_ref, ref_config),
+            Err(err) => tracing::warn!(
+                "Skipping ref {}, unable to get its story book config at {}\n{}",
+                story_book_ref.id,
+                story_book_ref.url,
+                err
+            ),
+        }
+    }
+}
+
+async fn get_manager_page(url: &str) -> Result<String, Error> {
+    let response = reqwest::get(format!("{}/index.html", url))
+        .await?
+        .error_for_status()?;
+
+    Ok(response.text().await?)
+}
 
-    Ok(config)
+/// Reads the refs assigned in the manager page, resolving their urls against the host
+fn parse_story_book_refs(html: &str, url: &str) -> Result<Vec<StoryBookRef>, Error> {
+    let Some(assignment) = REFS_ASSIGNMENT.find(html) else {
+        return Ok(vec![]);
+    };
+
+    // Only the object is json, the rest of the script is not
+    let refs = match serde_json::Deserializer::from_str(&html[assignment.end()..])
+        .into_iter::<HashMap<String, StoryBookRefConfig>>()
+        .next()
+    {
+        Some(refs) => refs?,
+        None => return Ok(vec![]),
+    };
+
+    let host = Url::parse(&format!("{}/", url.trim_end_matches('/')))?;
+
+    let mut refs = refs
+        .into_iter()
+        .map(|(id, config)| {
+            Ok(StoryBookRef {
+                title: config.title.unwrap_or_else(|| id.clone()),
+                url: host
+                    .join(&config.url)?
+                    .as_str()
+                    .trim_end_matches('/')
+                    .to_string(),
+                id,
+            })
+        })
+        .collect::<Result<Vec<StoryBookRef>, Error>>()?;
+    refs.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(refs)
+}
+
+/// Adds the entries of a ref to the index of the host under their composed ids
+fn add_ref_entries(
+    config: &mut StoryBookConfig,
+    story_book_ref: &StoryBookRef,
+    ref_config: StoryBookConfig,
+) {
+    config
+        .entries
+        .extend(ref_config.entries.into_values().map(|mut entry| {
+            entry.story_book_ref = Some(story_book_ref.clone());
+            (entry.composed_id(), entry)
+        }));
+}
+
+/// Checks the version of an index and normalizes its stories into entries
+fn parse_story_book_index(body: &str) -> Result<StoryBookConfig, Error> {
+    let index: StoryBookIndex = serde_json::from_str(body)?;
+
+    let entries = match (index.v, index.entries, index.stories) {
+        (4 | 5, Some(entries), _) => entries,
+        (3, _, Some(stories)) => stories
+            .into_iter()
+            .map(|(id, story)| (id, story.into_entry()))
+            .collect(),
+        (v, _, _) => {
+            return Err(Error::msg(format!(
+                "Unsupported index version {}, expected 3, 4 or 5",
+                v
+            )))
+        }
+    };
+
+    Ok(StoryBookConfig {
+        v: index.v,
+        entries,
+    })
 }
 
 fn get_screen_shot_params_from_config(
     config: StoryBookConfig,
     url: &str,
     image_type: &SnapShotType,
+    options: &CaptureOptions,
 ) -> Vec<ScreenShotParams> {
+    let devices = options.devices();
+
     config
         .entries
         .into_iter()
-        .map(|entry| ScreenShotParams {
-            url: format!("{}/iframe.html?args=&id={}&viewMode=story", url, entry.1.id),
-            name: entry.1.id.clone(),
-            id: entry.1.id,
-            image_type: *image_type,
+        .flat_map(|entry| {
+            let kind = match entry.1.r#type.as_str() {
+                "docs" => StoryKind::Docs,
+                _ => StoryKind::Story,
+            };
+            let view_mode = match kind {
+                StoryKind::Story => "story",
+                StoryKind::Docs => "docs",
+            };
+            // Stories of refs are rendered by the Storybook of their ref
+            let url = entry
+                .1
+                .story_book_ref
+                .as_ref()
+                .map_or(url, |story_book_ref| story_book_ref.url.as_str());
+            let id = entry.1.composed_id();
+            let title = entry.1.composed_title();
+
+            let params = ScreenShotParams {
+                url: format!(
+                    "{}/iframe.html?args=&id={}&viewMode={}",
+                    url, entry.1.id, view_mode
+                ),
+                name: id.clone(),
+                id: id.clone(),
+                image_type: *image_type,
+                viewport: None,
+                browser: Browser::default(),
+                globals: Globals::new(),
+                interaction: None,
+                media: None,
+                device: None,
+                story: Story {
+                    id: id.clone(),
+                    title: title.clone(),
+                    name: entry.1.name.clone(),
+                    import_path: entry.1.import_path.clone(),
+                    tags: entry.1.tags.clone(),
+                    kind,
+                },
+            };
+
+            // Docs render no story, so there are no args to vary
+            let variants = options
+                .args_variants
+                .iter()
+                .filter(|_| kind == StoryKind::Story)
+                .filter(|variant| variant.applies_to(&id, &title))
+                .map(|variant| ScreenShotParams {
+                    url: format!(
+                        "{}/iframe.html?args={}&id={}&viewMode=story",
+                        url,
+                        variant.to_query(),
+                        entry.1.id
+                    ),
+                    name: format!("{}+{}", params.id, variant.name),
+                    ..params.clone()
+                })
+                .collect::<Vec<ScreenShotParams>>();
+
+            std::iter::once(params).chain(variants)
+        })
+        .flat_map(|params| {
+            let interactions = options
+                .interactions
+                .iter()
+                .filter(|_| params.story.kind == StoryKind::Story)
+                .filter(|interaction| interaction.applies_to(&params.id))
+                .map(|interaction| ScreenShotParams {
+                    name: format!("{}!{}", params.name, interaction.state),
+                    interaction: Some(interaction.clone()),
+                    ..params.clone()
+                })
+                .collect::<Vec<ScreenShotParams>>();
+
+            std::iter::once(params).chain(interactions)
+        })
+        .flat_map(|params| {
+            let media = options
+                .media
+                .iter()
+                .map(|media| ScreenShotParams {
+                    name: format!("{}${}", params.name, media),
+                    media: Some(*media),
+                    ..params.clone()
+                })
+                .collect::<Vec<ScreenShotParams>>();
+
+            std::iter::once(params).chain(media)
+        })
+        .flat_map(|params| {
+            if options.viewports.is_empty() && devices.is_empty() {
+                return vec![params];
+            }
+
+            // Images are matched by name, so each viewport and device needs its own
+            options
+                .viewports
+                .iter()
+                .map(|viewport| (viewport.clone(), None))
+                .chain(
+                    devices
+                        .iter()
+                        .map(|device| (device.viewport(), Some(device.clone()))),
+                )
+                .map(|(viewport, device)| ScreenShotParams {
+                    name: format!("{}@{}", params.name, viewport.name),
+                    viewport: Some(viewport),
+                    device,
+                    ..params.clone()
+                })
+                .collect()
+        })
+        .flat_map(|params| {
+            if options.browsers.is_empty() {
+                return vec![params];
+            }
+
+            options
+                .browsers
+                .iter()
+                .map(|browser| ScreenShotParams {
+                    name: format!("{}~{}", params.name, browser),
+                    browser: *browser,
+                    ..params.clone()
+                })
+                .collect()
+        })
+        .flat_map(|params| {
+            if options.globals.is_empty() {
+                return vec![params];
+            }
+
+            globals::combinations(&options.globals)
+                .into_iter()
+                .map(|globals| ScreenShotParams {
+                    url: format!("{}&globals={}", params.url, globals::to_query(&globals)),
+                    name: format!("{}{}", params.name, globals::to_name_suffix(&globals)),
+                    globals,
+                    ..params.clone()
+                })
+                .collect()
         })
         .collect()
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
     use super::*;
+    use crate::models::{
+        args_variant::ArgsVariant,
+        device::DeviceProfile,
+        interaction::{Interaction, InteractionState},
+        media::MediaEmulation,
+        viewport::Viewport,
+    };
+
+    fn config() -> StoryBookConfig {
+        StoryBookConfig {
+            v: 5,
+            entries: HashMap::from([(
+                "button--primary".to_string(),
+                StoryBookConfigEntry {
+                    id: "button--primary".to_string(),
+                    name: "Primary".to_string(),
+                    title: "Button".to_string(),
+                    r#type: "story".to_string(),
+                    import_path: Some("./Button.stories.tsx".to_string()),
+                    tags: vec![],
+                    story_book_ref: None,
+                },
+            )]),
+        }
+    }
 
     #[tokio::test]
     async fn test_get_screenshot_params_by_url() {
         let url = "https://ec.europa.eu/component-library/playground/eu";
         let image_type = SnapShotType::Old;
 
-        let result = get_screenshot_params_by_url(url, &image_type).await;
+        let result =
+            get_screenshot_params_by_url(url, &image_type, &CaptureOptions::default()).await;
 
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_parse_story_book_index() {
+        for v in [4, 5] {
+            let config = parse_story_book_index(&format!(
+                r#"{{ "v": {}, "entries": {{
+                    "button--primary": {{ "id": "button--primary", "name": "Primary", "title": "Button", "type": "story", "importPath": "./Button.stories.tsx", "tags": ["story"] }},
+                    "button--docs": {{ "id": "button--docs", "name": "Docs", "title": "Button", "type": "docs", "importPath": "./Button.mdx" }}
+                }} }}"#,
+                v
+            ))
+            .unwrap();
+
+            assert_eq!(config.v, v);
+            assert_eq!(config.entries.len(), 2);
+            assert_eq!(config.entries["button--docs"].r#type, "docs");
+            assert_eq!(
+                config.entries["button--primary"].import_path.as_deref(),
+                Some("./Button.stories.tsx")
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_story_book_stories_json() {
+        let config = parse_story_book_index(
+            r#"{ "v": 3, "stories": {
+                "button--primary": { "id": "button--primary", "name": "Primary", "title": "Button", "kind": "Button", "importPath": "./Button.stories.tsx", "parameters": { "__id": "button--primary" } },
+                "card--default": { "id": "card--default", "name": "Default", "kind": "Card", "parameters": {} },
+                "intro--page": { "id": "intro--page", "name": "Page", "kind": "Intro", "parameters": { "docsOnly": true } }
+            } }"#,
+        )
+        .unwrap();
+
+        let button = &config.entries["button--primary"];
+        assert_eq!(
+            (button.title.as_str(), button.r#type.as_str()),
+            ("Button", "story")
+        );
+        assert_eq!(config.entries["card--default"].title, "Card");
+        assert_eq!(config.entries["intro--page"].r#type, "docs");
+    }
+
+    #[test]
+    fn test_parse_story_book_index_unsupported_version() {
+        let err = parse_story_book_index(r#"{ "v": 2, "stories": {} }"#).unwrap_err();
+        assert!(err.to_string().contains("Unsupported index version 2"));
+
+        // A version 3 index has stories rather than entries
+        assert!(parse_story_book_index(r#"{ "v": 3, "entries": {} }"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_story_book_refs() {
+        let html = r#"<script>
+            window['LOGLEVEL'] = "info";
+            window['REFS'] = {"forms":{"title":"Forms","url":"https://forms.example.com/"},"icons":{"url":"/icons"}};
+            window['DOCS_OPTIONS'] = {};
+        </script>"#;
+
+        let refs = parse_story_book_refs(html, "http://localhost/host").unwrap();
+
+        assert_eq!(
+            refs,
+            vec![
+                StoryBookRef {
+                    id: "forms".to_string(),
+                    title: "Forms".to_string(),
+                    url: "https://forms.example.com".to_string(),
+                },
+                StoryBookRef {
+                    id: "icons".to_string(),
+                    title: "icons".to_string(),
+                    url: "http://localhost/icons".to_string(),
+                },
+            ]
+        );
+        assert!(parse_story_book_refs("<html></html>", "http://localhost")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_add_story_book_refs_skips_broken_refs() {
+        let index = json!({
+            "v": 5,
+            "entries": {
+                "input--default": {
+                    "id": "input--default",
+                    "name": "Default",
+                    "title": "Input",
+                    "type": "story",
+                    "importPath": "./Input.stories.tsx",
+                    "tags": []
+                }
+            }
+        });
+
+        // Serves the index of the forms ref, a broken one of the icons ref and none of the missing ref
+        let app = axum::Router::new()
+            .route(
+                "/forms/index.json",
+                axum::routing::get(move || async move { axum::Json(index) }),
+            )
+            .route(
+                "/icons/index.json",
+                axum::routing::get(|| async { "{ not an index" }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let story_book_ref = |id: &str| StoryBookRef {
+            id: id.to_string(),
+            title: id.to_string(),
+            url: format!("http://{}/{}", address, id),
+        };
+
+        let mut host = config();
+        add_story_book_refs(
+            &mut host,
+            vec![
+                story_book_ref("icons"),
+                story_book_ref("forms"),
+                story_book_ref("missing"),
+            ],
+        )
+        .await;
+
+        let mut ids: Vec<&String> = host.entries.keys().collect();
+        ids.sort();
+
+        assert_eq!(ids, vec!["button--primary", "forms_input--default"]);
+    }
+
+    #[test]
+    fn test_get_screen_shot_params_from_config_with_refs() {
+        let mut host = config();
+        add_ref_entries(
+            &mut host,
+            &StoryBookRef {
+                id: "forms".to_string(),
+                title: "Forms".to_string(),
+                url: "https://forms.example.com".to_string(),
+            },
+            config(),
+        );
+
+        let mut params = get_screen_shot_params_from_config(
+            host,
+            "http://localhost",
+            &SnapShotType::New,
+            &CaptureOptions::default(),
+        );
+        params.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let names: Vec<String> = params.iter().map(|param| param.name.clone()).collect();
+
+        assert_eq!(names, vec!["button--primary", "forms_button--primary"]);
+        assert_eq!(
+            params[1].url,
+            "https://forms.example.com/iframe.html?args=&id=button--primary&viewMode=story"
+        );
+        assert_eq!(params[1].story.id, "forms_button--primary");
+        assert_eq!(params[1].story.title, "Forms/Button");
+    }
+
+    #[test]
+    fn test_select_stories() {
+        let entry = |id: &str, r#type: &str, tags: &[&str]| {
+            (
+                id.to_string(),
+                StoryBookConfigEntry {
+                    id: id.to_string(),
+                    name: "Primary".to_string(),
+                    title: "Button".to_string(),
+                    r#type: r#type.to_string(),
+                    import_path: None,
+                    tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                    story_book_ref: None,
+                },
+            )
+        };
+
+        let config = || StoryBookConfig {
+            v: 5,
+            entries: HashMap::from([
+                entry("button--primary", "story", &["dev"]),
+                entry("button--skipped", "story", &["no-visual-test"]),
+                entry("button--docs", "docs", &["dev"]),
+                entry("button--deprecated", "story", &["dev"]),
+            ]),
+        };
+
+        let filter = StoryFilter {
+            include: vec!["Button".to_string()],
+            exclude: vec!["*--deprecated".to_string()],
+            tags: vec!["!no-visual-test".to_string()],
+        };
+
+        let selected = select_stories(config(), &filter, false);
+
+        assert_eq!(
+            selected.entries.keys().collect::<Vec<&String>>(),
+            vec!["button--primary"]
+        );
+
+        let selected = select_stories(config(), &filter, true);
+        let mut ids = selected.entries.keys().collect::<Vec<&String>>();
+        ids.sort();
+
+        assert_eq!(ids, vec!["button--docs", "button--primary"]);
+    }
+
+    #[test]
+    fn test_get_screen_shot_params_from_config_with_viewports() {
+        let options = CaptureOptions {
+            viewports: vec![
+                Viewport {
+                    name: "mobile".to_string(),
+                    width: 375,
+                    height: 667,
+                },
+                Viewport {
+                    name: "desktop".to_string(),
+                    width: 1280,
+                    height: 800,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let params = get_screen_shot_params_from_config(
+            config(),
+            "http://localhost",
+            &SnapShotType::New,
+            &options,
+        );
+
+        let names: Vec<String> = params.iter().map(|param| param.name.clone()).collect();
+
+        assert_eq!(
+            names,
+            vec!["button--primary@mobile", "button--primary@desktop"]
+        );
+        assert!(params.iter().all(|param| param.id == "button--primary"));
+        assert_eq!(params[0].viewport.as_ref().unwrap().width, 375);
+    }
+
+    #[test]
+    fn test_get_screen_shot_params_from_config_with_browsers() {
+        let options = CaptureOptions {
+            viewports: vec![Viewport {
+                name: "mobile".to_string(),
+                width: 375,
+                height: 667,
+            }],
+            browsers: vec![Browser::Chrome, Browser::Firefox],
+            ..Default::default()
+        };
+
+        let params = get_screen_shot_params_from_config(
+            config(),
+            "http://localhost",
+            &SnapShotType::New,
+            &options,
+        );
+
+        let names: Vec<String> = params.iter().map(|param| param.name.clone()).collect();
+
+        assert_eq!(
+            names,
+            vec![
+                "button--primary@mobile~chrome",
+                "button--primary@mobile~firefox"
+            ]
+        );
+        assert_eq!(params[1].browser, Browser::Firefox);
+    }
+
+    #[test]
+    fn test_get_screen_shot_params_from_config_with_globals() {
+        let options = CaptureOptions {
+            globals: globals::GlobalsMatrix::from([
+                (
+                    "theme".to_string(),
+                    vec!["light".to_string(), "dark".to_string()],
+                ),
+                ("locale".to_string(), vec!["fr".to_string()]),
+            ]),
+            ..Default::default()
+        };
+
+        let params = get_screen_shot_params_from_config(
+            config(),
+            "http://localhost",
+            &SnapShotType::New,
+            &options,
+        );
+
+        let names: Vec<String> = params.iter().map(|param| param.name.clone()).collect();
+
+        assert_eq!(
+            names,
+            vec![
+                "button--primary,locale=fr,theme=light",
+                "button--primary,locale=fr,theme=dark"
+            ]
+        );
+        assert_eq!(
+            params[1].url,
+            "http://localhost/iframe.html?args=&id=button--primary&viewMode=story&globals=locale:fr;theme:dark"
+        );
+        assert_eq!(params[1].globals["theme"], "dark");
+    }
+
+    #[test]
+    fn test_get_screen_shot_params_from_config_with_args_variants() {
+        let options = CaptureOptions {
+            viewports: vec![Viewport {
+                name: "mobile".to_string(),
+                width: 375,
+                height: 667,
+            }],
+            args_variants: vec![
+                ArgsVariant {
+                    name: "disabled".to_string(),
+                    args: BTreeMap::from([("disabled".to_string(), json!(true))]),
+                    stories: vec![],
+                    titles: vec!["Butt*".to_string()],
+                },
+                ArgsVariant {
+                    name: "loading".to_string(),
+                    args: BTreeMap::from([("loading".to_string(), json!(true))]),
+                    stories: vec!["card--default".to_string()],
+                    titles: vec![],
+                },
+            ],
+            ..Default::default()
+        };
+
+        let mut params = get_screen_shot_params_from_config(
+            config(),
+            "http://localhost",
+            &SnapShotType::New,
+            &options,
+        );
+        params.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let names: Vec<String> = params.iter().map(|param| param.name.clone()).collect();
+
+        assert_eq!(
+            names,
+            vec!["button--primary+disabled@mobile", "button--primary@mobile"]
+        );
+        assert_eq!(
+            params[0].url,
+            "http://localhost/iframe.html?args=disabled:!true&id=button--primary&viewMode=story"
+        );
+        assert_eq!(params[0].id, "button--primary");
+    }
+
+    #[test]
+    fn test_get_screen_shot_params_from_config_with_interactions() {
+        let options = CaptureOptions {
+            interactions: vec![
+                Interaction {
+                    state: InteractionState::FocusVisible,
+                    selector: "button".to_string(),
+                    stories: vec![],
+                },
+                Interaction {
+                    state: InteractionState::Hover,
+                    selector: "button".to_string(),
+                    stories: vec!["card--default".to_string()],
+                },
+            ],
+            ..Default::default()
+        };
+
+        let params = get_screen_shot_params_from_config(
+            config(),
+            "http://localhost",
+            &SnapShotType::New,
+            &options,
+        );
+
+        let names: Vec<String> = params.iter().map(|param| param.name.clone()).collect();
+
+        assert_eq!(
+            names,
+            vec!["button--primary", "button--primary!focus-visible"]
+        );
+        assert_eq!(params[0].interaction, None);
+        assert_eq!(params[1].url, params[0].url);
+        assert_eq!(
+            params[1]
+                .interaction
+                .as_ref()
+                .map(|interaction| interaction.state),
+            Some(InteractionState::FocusVisible)
+        );
+    }
+
+    #[test]
+    fn test_get_screen_shot_params_from_config_with_media() {
+        let options = CaptureOptions {
+            viewports: vec![Viewport {
+                name: "mobile".to_string(),
+                width: 375,
+                height: 667,
+            }],
+            media: vec![MediaEmulation::Dark, MediaEmulation::ForcedColors],
+            ..Default::default()
+        };
+
+        let params = get_screen_shot_params_from_config(
+            config(),
+            "http://localhost",
+            &SnapShotType::New,
+            &options,
+        );
+
+        let names: Vec<String> = params.iter().map(|param| param.name.clone()).collect();
+
+        assert_eq!(
+            names,
+            vec![
+                "button--primary@mobile",
+                "button--primary$dark@mobile",
+                "button--primary$forced-colors@mobile"
+            ]
+        );
+        assert_eq!(params[0].media, None);
+        assert_eq!(params[1].media, Some(MediaEmulation::Dark));
+        assert_eq!(params[1].url, params[0].url);
+    }
+
+    #[test]
+    fn test_get_screen_shot_params_from_config_with_docs() {
+        let mut config = config();
+        config.entries.insert(
+            "button--docs".to_string(),
+            StoryBookConfigEntry {
+                id: "button--docs".to_string(),
+                name: "Docs".to_string(),
+                title: "Button".to_string(),
+                r#type: "docs".to_string(),
+                import_path: Some("./Button.mdx".to_string()),
+                tags: vec![],
+                story_book_ref: None,
+            },
+        );
+        let options = CaptureOptions {
+            docs: true,
+            interactions: vec![Interaction {
+                state: InteractionState::Hover,
+                selector: "button".to_string(),
+                stories: vec![],
+            }],
+            media: vec![MediaEmulation::Dark],
+            ..Default::default()
+        };
+
+        let mut params = get_screen_shot_params_from_config(
+            config,
+            "http://localhost",
+            &SnapShotType::New,
+            &options,
+        );
+        params.retain(|param| param.id == "button--docs");
+
+        let names: Vec<String> = params.iter().map(|param| param.name.clone()).collect();
+
+        assert_eq!(names, vec!["button--docs", "button--docs$dark"]);
+        assert_eq!(
+            params[0].url,
+            "http://localhost/iframe.html?args=&id=button--docs&viewMode=docs"
+        );
+        assert_eq!(params[0].story.kind, StoryKind::Docs);
+    }
+
+    #[test]
+    fn test_get_screen_shot_params_from_config_with_devices() {
+        let options = CaptureOptions {
+            viewports: vec![Viewport {
+                name: "mobile".to_string(),
+                width: 375,
+                height: 667,
+            }],
+            devices: vec![DeviceProfile::Preset("iphone-14".to_string())],
+            ..Default::default()
+        };
+
+        let params = get_screen_shot_params_from_config(
+            config(),
+            "http://localhost",
+            &SnapShotType::New,
+            &options,
+        );
+
+        let names: Vec<String> = params.iter().map(|param| param.name.clone()).collect();
+
+        assert_eq!(
+            names,
+            vec!["button--primary@mobile", "button--primary@iphone-14"]
+        );
+        assert_eq!(params[0].device, None);
+        assert_eq!(
+            params[1].viewport.as_ref().map(|viewport| viewport.width),
+            Some(390)
+        );
+        assert_eq!(
+            params[1]
+                .device
+                .as_ref()
+                .map(|device| device.device_pixel_ratio),
+            Some(3.0)
+        );
+    }
 }